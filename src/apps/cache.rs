@@ -0,0 +1,109 @@
+use crate::{
+    apps::{system::DesktopFiles, SystemApps},
+    error::Result,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// On-disk cache of installed applications, so desktop entries
+/// do not have to be parsed every time handlr is run
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct SystemAppsCache {
+    /// Version of handlr that wrote the cache, in case the format changes
+    version: String,
+    /// `applications` directories the cache was built from, in order of precedence,
+    /// and the time of the most recent change within each of them
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    /// The cached applications
+    pub(super) system_apps: SystemApps,
+}
+
+impl SystemAppsCache {
+    /// Create a new cache of the given installed applications,
+    /// given the `applications` directories they were read from
+    pub(super) fn new(
+        dirs: &[(PathBuf, DesktopFiles)],
+        system_apps: SystemApps,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            dirs: Self::key(dirs),
+            system_apps,
+        }
+    }
+
+    /// Get the path of the cache file
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub(super) fn path() -> Result<PathBuf> {
+        Ok(xdg::BaseDirectories::new()?
+            .place_cache_file("handlr/system_apps.json")?)
+    }
+
+    /// Get the directories and modification times used to check if the cache is up to date
+    fn key(
+        dirs: &[(PathBuf, DesktopFiles)],
+    ) -> Vec<(PathBuf, Option<SystemTime>)> {
+        dirs.iter()
+            .map(|(dir, files)| (dir.clone(), files.modified))
+            .collect_vec()
+    }
+
+    /// Check if the cache is up to date with the given `applications` directories
+    pub(super) fn is_fresh(&self, dirs: &[(PathBuf, DesktopFiles)]) -> bool {
+        self.version == env!("CARGO_PKG_VERSION")
+            && self.dirs == Self::key(dirs)
+    }
+
+    /// Read the cache from a given path, if it exists and is valid
+    pub(super) fn read(path: &Path) -> Option<Self> {
+        serde_json::from_reader(BufReader::new(File::open(path).ok()?)).ok()
+    }
+
+    /// Write the cache to a given path
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::DesktopHandler;
+
+    #[test]
+    fn cache_round_trip() -> Result<()> {
+        let walk = || {
+            let dir = PathBuf::from("./tests/assets/data_dirs/applications");
+            let files = DesktopFiles::walk(&dir);
+            vec![(dir, files)]
+        };
+
+        let mut system_apps = SystemApps::default();
+        system_apps.add_unassociated(DesktopHandler::assume_valid(
+            "bar.desktop".into(),
+        ));
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("cache.json");
+        SystemAppsCache::new(&walk(), system_apps).write(&path)?;
+
+        let cache = SystemAppsCache::read(&path).expect("Could not read cache");
+
+        assert!(cache.is_fresh(&walk()));
+        assert!(!cache.is_fresh(&[]));
+        assert!(cache.system_apps.terminal_emulator().is_none());
+
+        let mut dirs = walk();
+        dirs[0].1.modified = Some(SystemTime::UNIX_EPOCH);
+        assert!(!cache.is_fresh(&dirs));
+
+        Ok(())
+    }
+}
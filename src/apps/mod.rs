@@ -1,5 +1,6 @@
+mod cache;
 mod system;
 mod user;
 
 pub use system::SystemApps;
-pub use user::{DesktopList, MimeApps};
+pub use user::{select, DesktopList, MimeApps};
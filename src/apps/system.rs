@@ -1,15 +1,28 @@
 use crate::{
-    apps::DesktopList,
-    common::{DesktopEntry, DesktopHandler, Handleable},
+    apps::{cache::SystemAppsCache, DesktopList},
+    common::{DesktopEntry, DesktopHandler, Handleable, IniDocument},
+    config::Trace,
     error::Result,
 };
+use itertools::Itertools;
 use mime::Mime;
-use std::{collections::BTreeMap, convert::TryFrom, ffi::OsString};
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::TryFrom,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+use tracing::{debug, warn};
 
-#[derive(Debug, Default, Clone)]
+#[serde_as]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SystemApps {
     /// Associations of mimes and lists of apps
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
     pub associations: BTreeMap<Mime, DesktopList>,
     /// Apps with no associated mime
     unassociated: DesktopList,
@@ -32,58 +45,205 @@ impl SystemApps {
         Some(associations?.clone())
     }
 
-    /// Get the primary of handler associated with a given mime
-    pub fn get_handler(&self, mime: &Mime) -> Option<DesktopHandler> {
-        let handler = self.get_handlers(mime)?.front()?.clone();
+    /// Get the primary of handler associated with a given mime,
+    /// skipping any handlers whose association with it has been removed
+    /// and any that are unavailable in the current desktop environment
+    pub fn get_handler(
+        &self,
+        mime: &Mime,
+        removed: &DesktopList,
+        trace: &mut Trace,
+    ) -> Option<DesktopHandler> {
+        let handler = self
+            .get_handlers(mime)?
+            .iter()
+            .find(|h| {
+                if removed.contains(h) {
+                    debug!("Skipping removed association `{}`", h);
+                    trace.skip(h, "association removed");
+                    return false;
+                }
+
+                // Only skip entries that are known to be unavailable
+                let is_available =
+                    h.get_entry().map_or(true, |entry| entry.is_available());
+                if !is_available {
+                    debug!("Skipping unavailable handler `{}`", h);
+                    trace.skip(h, "unavailable");
+                }
+                is_available
+            })?
+            .clone();
         debug!("Installed handler chosen for `{}`: {}", mime, handler);
+        trace.choose(&handler, "installed applications");
         Some(handler)
     }
 
-    /// Get all system-level desktop entries on the system
+    /// Get the `applications` directories in the XDG data dirs, in order of precedence
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub fn applications_dirs() -> Result<Vec<PathBuf>> {
+        let base_dirs = xdg::BaseDirectories::new()?;
+
+        Ok(std::iter::once(base_dirs.get_data_home())
+            .chain(base_dirs.get_data_dirs())
+            .map(|dir| dir.join("applications"))
+            .unique()
+            .collect_vec())
+    }
+
+    /// Find the desktop entry file for a given desktop file ID
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub fn find_desktop_file(id: &OsStr) -> Result<Option<PathBuf>> {
+        Ok(Self::find_desktop_file_in(Self::applications_dirs()?, id))
+    }
+
+    /// Find the desktop entry file for a given desktop file ID in the given `applications` directories
+    /// The first directory containing it takes precedence
+    fn find_desktop_file_in(
+        dirs: impl IntoIterator<Item = PathBuf>,
+        id: &OsStr,
+    ) -> Option<PathBuf> {
+        dirs.into_iter().find_map(|dir| match id.to_str() {
+            Some(id) => resolve_desktop_id(&dir, id),
+            None => Some(dir.join(id)).filter(|path| path.is_file()),
+        })
+    }
+
+    /// Get all system-level desktop entries on the system, keyed by desktop file ID
     #[mutants::skip] // Cannot test directly, depends on system state
     pub fn get_entries(
     ) -> Result<impl Iterator<Item = (OsString, DesktopEntry)>> {
-        Ok(xdg::BaseDirectories::new()?
-            .list_data_files_once("applications")
+        let mut seen = HashSet::new();
+
+        Ok(Self::applications_dirs()?
             .into_iter()
-            .filter(|p| {
-                p.extension().and_then(|x| x.to_str()) == Some("desktop")
-            })
-            .filter_map(|p| {
-                Some((
-                    p.file_name()?.to_owned(),
-                    DesktopEntry::try_from(p.clone()).ok()?,
-                ))
+            .flat_map(|dir| DesktopFiles::walk(&dir).files)
+            // Entries in earlier directories shadow those with the same ID in later ones
+            .filter(move |(id, _)| seen.insert(id.clone()))
+            .filter_map(|(id, path)| {
+                Some((id, DesktopEntry::try_from(path).ok()?))
             }))
     }
 
     /// Create a new instance of `SystemApps`
+    /// Loads from the cache if it is up to date, otherwise populates and updates the cache
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub fn load() -> Result<Self> {
+        let dirs = Self::walk_applications_dirs()?;
+        let cache_path = SystemAppsCache::path()?;
+
+        match SystemAppsCache::read(&cache_path) {
+            Some(cache) if cache.is_fresh(&dirs) => Ok(cache.system_apps),
+            _ => Self::populate_and_cache(dirs, &cache_path),
+        }
+    }
+
+    /// Create a new instance of `SystemApps`, ignoring any existing cache, and update the cache
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub fn rebuild_cache() -> Result<Self> {
+        Self::populate_and_cache(
+            Self::walk_applications_dirs()?,
+            &SystemAppsCache::path()?,
+        )
+    }
+
+    /// Create a new instance of `SystemApps` and write it to the cache at the given path
+    /// Failing to write the cache is not fatal
+    #[mutants::skip] // Cannot test directly, depends on system state
+    fn populate_and_cache(
+        dirs: Vec<(PathBuf, DesktopFiles)>,
+        cache_path: &Path,
+    ) -> Result<Self> {
+        let cache = SystemAppsCache::new(&dirs, Self::populate_from(&dirs));
+
+        if let Err(e) = cache.write(cache_path) {
+            warn!("Could not write cache of installed applications: {}", e);
+        }
+
+        Ok(cache.system_apps)
+    }
+
+    /// Find the desktop entry files in every `applications` directory
     #[mutants::skip] // Cannot test directly, depends on system state
-    pub fn populate() -> Result<Self> {
+    fn walk_applications_dirs() -> Result<Vec<(PathBuf, DesktopFiles)>> {
+        Ok(Self::applications_dirs()?
+            .into_iter()
+            .map(|dir| {
+                let files = DesktopFiles::walk(&dir);
+                (dir, files)
+            })
+            .collect_vec())
+    }
+
+    /// Create a new instance of `SystemApps` from the given `applications` directories
+    /// and the desktop entry files in them, in order of precedence
+    fn populate_from(dirs: &[(PathBuf, DesktopFiles)]) -> Self {
         let mut associations = BTreeMap::<Mime, DesktopList>::new();
         let mut unassociated = DesktopList::default();
+        // Desktop file IDs found in directories with higher precedence
+        let mut seen = HashSet::<OsString>::new();
 
-        Self::get_entries()?.for_each(|(_, entry)| {
-            let (file_name, mimes) = (entry.file_name, entry.mime_type);
-            let desktop_handler =
-                DesktopHandler::assume_valid(file_name.to_owned());
+        for (dir, files) in dirs {
+            let mut associated = HashSet::<OsString>::new();
 
-            if mimes.is_empty() {
-                unassociated.push_back(desktop_handler);
-            } else {
-                mimes.into_iter().for_each(|mime| {
+            let mut associate = |mime: Mime, id: &OsString| {
+                if !seen.contains(id) {
+                    associated.insert(id.clone());
                     associations
                         .entry(mime)
                         .or_default()
-                        .push_back(desktop_handler.clone());
-                });
+                        .push_back(DesktopHandler::assume_valid(id.clone()));
+                }
+            };
+
+            match files.read_cache(dir) {
+                Some(cache) => {
+                    cache
+                        .into_iter()
+                        .flat_map(|(mime, ids)| {
+                            ids.into_iter().map(move |id| (mime.clone(), id))
+                        })
+                        .filter(|(_, id)| files.contains(id))
+                        .for_each(|(mime, id)| associate(mime, &id));
+                }
+                // Parse every desktop entry if the cache is missing or stale
+                None => {
+                    files
+                        .files
+                        .iter()
+                        .filter_map(|(id, path)| {
+                            Some((
+                                id,
+                                DesktopEntry::try_from(path.clone()).ok()?,
+                            ))
+                        })
+                        .for_each(|(id, entry)| {
+                            entry
+                                .mime_type
+                                .into_iter()
+                                .for_each(|mime| associate(mime, id));
+                        });
+                }
             }
-        });
 
-        Ok(Self {
+            files
+                .files
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !associated.contains(*id))
+                .filter(|id| seen.insert((*id).clone()))
+                .for_each(|id| {
+                    unassociated
+                        .push_back(DesktopHandler::assume_valid(id.clone()))
+                });
+
+            seen.extend(associated);
+        }
+
+        Self {
             associations,
             unassociated,
-        })
+        }
     }
 
     /// Get an installed terminal emulator
@@ -91,7 +251,7 @@ impl SystemApps {
         self.unassociated
             .iter()
             .filter_map(|h| h.get_entry().ok())
-            .find(|h| h.is_terminal_emulator())
+            .find(|h| h.is_terminal_emulator() && h.is_available())
     }
 
     #[cfg(test)]
@@ -101,6 +261,138 @@ impl SystemApps {
     }
 }
 
+/// Name of the cache of mime associations generated by `update-desktop-database`
+const MIMEINFO_CACHE: &str = "mimeinfo.cache";
+
+/// Desktop entry files in an `applications` directory
+#[derive(Debug, Default)]
+pub(super) struct DesktopFiles {
+    /// Desktop file IDs and the paths of their files, sorted by ID
+    files: Vec<(OsString, PathBuf)>,
+    /// Time of the most recent change to the directory or any file or subdirectory within it
+    pub(super) modified: Option<SystemTime>,
+    /// Time of the most recent change to any desktop entry file or subdirectory
+    /// The directory itself is left out, since writing mimeinfo.cache into it changes it too
+    entries_modified: Option<SystemTime>,
+}
+
+impl DesktopFiles {
+    /// Find all desktop entry files in a given `applications` directory
+    pub(super) fn walk(dir: &Path) -> Self {
+        let mut desktop_files = Self::default();
+        desktop_files.walk_subdir(dir, None);
+        desktop_files.files.sort();
+        desktop_files
+    }
+
+    /// Recursively find desktop entry files, given the desktop file ID prefix of the directory
+    fn walk_subdir(&mut self, dir: &Path, prefix: Option<&OsStr>) {
+        self.update_modified(dir, prefix.is_some());
+
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+
+            // Desktop file IDs replace directory separators with `-`
+            let id = prefix.map_or_else(
+                || name.clone(),
+                |prefix| {
+                    let mut id = prefix.to_owned();
+                    id.push("-");
+                    id.push(&name);
+                    id
+                },
+            );
+
+            if path.is_dir() {
+                self.walk_subdir(&path, Some(&id));
+            } else if path.extension().and_then(|x| x.to_str())
+                == Some("desktop")
+            {
+                self.update_modified(&path, true);
+                self.files.push((id, path));
+            }
+        }
+    }
+
+    /// Keep track of the most recent modification time,
+    /// given whether the path is a desktop entry file or subdirectory
+    fn update_modified(&mut self, path: &Path, is_entry: bool) {
+        let Ok(modified) =
+            std::fs::metadata(path).and_then(|metadata| metadata.modified())
+        else {
+            return;
+        };
+
+        self.modified = self.modified.max(Some(modified));
+        if is_entry {
+            self.entries_modified = self.entries_modified.max(Some(modified));
+        }
+    }
+
+    /// Check if there is a desktop entry file with the given desktop file ID
+    fn contains(&self, id: &OsStr) -> bool {
+        self.files
+            .binary_search_by(|(other, _)| other.as_os_str().cmp(id))
+            .is_ok()
+    }
+
+    /// Read the `mimeinfo.cache` file in the given `applications` directory,
+    /// unless it is missing or older than any of the desktop entry files
+    fn read_cache(&self, dir: &Path) -> Option<BTreeMap<Mime, Vec<OsString>>> {
+        let path = dir.join(MIMEINFO_CACHE);
+        let cache_modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+
+        if self
+            .entries_modified
+            .is_some_and(|modified| modified > cache_modified)
+        {
+            return None;
+        }
+
+        let document =
+            IniDocument::parse(&std::fs::read_to_string(&path).ok()?);
+
+        Some(
+            document
+                .entries("MIME Cache")
+                .into_iter()
+                .filter_map(|(mime, ids)| {
+                    Some((
+                        Mime::from_str(mime).ok()?,
+                        ids.split(';')
+                            .filter(|id| !id.is_empty())
+                            .map(OsString::from)
+                            .collect_vec(),
+                    ))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Find the file for a given desktop file ID in a given directory
+/// Each `-` in the ID may correspond to a directory separator
+fn resolve_desktop_id(dir: &Path, id: &str) -> Option<PathBuf> {
+    let path = dir.join(id);
+
+    if path.is_file() {
+        return Some(path);
+    }
+
+    id.match_indices('-').find_map(|(index, _)| {
+        let subdir = dir.join(&id[..index]);
+        subdir
+            .is_dir()
+            .then(|| resolve_desktop_id(&subdir, &id[index + 1..]))
+            .flatten()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,7 +416,11 @@ mod tests {
 
         assert_eq!(
             system_apps
-                .get_handler(&mime::TEXT_PLAIN)
+                .get_handler(
+                    &mime::TEXT_PLAIN,
+                    &DesktopList::default(),
+                    &mut Trace::default()
+                )
                 .expect("Could not get handler")
                 .to_string(),
             "helix.desktop"
@@ -138,4 +434,190 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn skip_removed_associations() -> Result<()> {
+        let mut handlers = DesktopList::default();
+        handlers
+            .push_back(DesktopHandler::assume_valid("helix.desktop".into()));
+        handlers.push_back(DesktopHandler::assume_valid("nvim.desktop".into()));
+
+        let system_apps = SystemApps {
+            associations: BTreeMap::from([(mime::TEXT_PLAIN, handlers)]),
+            ..Default::default()
+        };
+
+        let mut removed = DesktopList::default();
+        removed.push_back(DesktopHandler::assume_valid("helix.desktop".into()));
+
+        assert_eq!(
+            system_apps
+                .get_handler(&mime::TEXT_PLAIN, &removed, &mut Trace::default())
+                .expect("Could not get handler")
+                .to_string(),
+            "nvim.desktop"
+        );
+
+        removed.push_back(DesktopHandler::assume_valid("nvim.desktop".into()));
+
+        assert!(system_apps
+            .get_handler(&mime::TEXT_PLAIN, &removed, &mut Trace::default())
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_unavailable_handlers() -> Result<()> {
+        let mut handlers = DesktopList::default();
+        handlers.push_back(DesktopHandler::assume_valid(
+            "tests/assets/hidden.desktop".into(),
+        ));
+        handlers.push_back(DesktopHandler::assume_valid(
+            "tests/assets/Helix.desktop".into(),
+        ));
+
+        let system_apps = SystemApps {
+            associations: BTreeMap::from([(mime::TEXT_PLAIN, handlers)]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            system_apps
+                .get_handler(
+                    &mime::TEXT_PLAIN,
+                    &DesktopList::default(),
+                    &mut Trace::default()
+                )
+                .expect("Could not get handler")
+                .to_string(),
+            "tests/assets/Helix.desktop"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn desktop_file_ids() {
+        let dirs = [
+            PathBuf::from("./tests/assets/data_home/applications"),
+            PathBuf::from("./tests/assets/data_dirs/applications"),
+        ];
+
+        assert_eq!(
+            SystemApps::find_desktop_file_in(
+                dirs.clone(),
+                OsStr::new("kde-foo.desktop")
+            ),
+            Some(PathBuf::from(
+                "./tests/assets/data_home/applications/kde/foo.desktop"
+            ))
+        );
+        assert_eq!(
+            SystemApps::find_desktop_file_in(
+                dirs.clone(),
+                OsStr::new("shadowed.desktop")
+            ),
+            Some(PathBuf::from(
+                "./tests/assets/data_home/applications/shadowed.desktop"
+            ))
+        );
+        assert_eq!(
+            SystemApps::find_desktop_file_in(dirs, OsStr::new("foo.desktop")),
+            None
+        );
+    }
+
+    // Helper function to copy a fixture directory so tests can modify it
+    fn copy_dir(from: &std::path::Path, to: &std::path::Path) -> Result<()> {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                copy_dir(&entry.path(), &target)?;
+            } else {
+                std::fs::copy(entry.path(), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn mimeinfo_cache() -> Result<()> {
+        // Work on a copy, since the cache's modification time is changed
+        let dir = tempfile::tempdir()?;
+        let data_home = dir.path().join("applications");
+        copy_dir(
+            std::path::Path::new("./tests/assets/data_home/applications"),
+            &data_home,
+        )?;
+        let dirs = || {
+            [
+                data_home.clone(),
+                PathBuf::from("./tests/assets/data_dirs/applications"),
+            ]
+            .into_iter()
+            .map(|dir| {
+                let files = DesktopFiles::walk(&dir);
+                (dir, files)
+            })
+            .collect_vec()
+        };
+        let cache_path = data_home.join(MIMEINFO_CACHE);
+
+        let handlers = |system_apps: &SystemApps, mime: &str| {
+            system_apps
+                .get_handlers(&Mime::from_str(mime).expect("Invalid mime"))
+                .map(|handlers| handlers.to_string())
+        };
+
+        // Regenerate the cache like update-desktop-database does,
+        // by writing a temporary file and renaming it into place,
+        // which makes the directory newer than the cache itself
+        let written = SystemTime::now();
+        let temp_path = data_home.join(".mimeinfo.cache.tmp");
+        std::fs::copy(&cache_path, &temp_path)?;
+        std::fs::File::options()
+            .write(true)
+            .open(&temp_path)?
+            .set_modified(written)?;
+        std::fs::rename(&temp_path, &cache_path)?;
+        std::fs::File::open(&data_home)?
+            .set_modified(written + std::time::Duration::from_secs(1))?;
+
+        // Fresh cache is used instead of the desktop entries' MimeType keys
+        let system_apps = SystemApps::populate_from(&dirs());
+
+        assert_eq!(
+            handlers(&system_apps, "text/x-cached"),
+            Some("kde-foo.desktop;".to_string())
+        );
+        assert_eq!(
+            handlers(&system_apps, "text/html"),
+            Some("shadowed.desktop;".to_string())
+        );
+        // The system-level shadowed.desktop is hidden by the user-level one
+        assert_eq!(handlers(&system_apps, "text/plain"), None);
+        assert_eq!(system_apps.unassociated.to_string(), "bar.desktop;");
+
+        // Stale cache is ignored
+        std::fs::File::options()
+            .write(true)
+            .open(&cache_path)?
+            .set_modified(SystemTime::UNIX_EPOCH)?;
+        let system_apps = SystemApps::populate_from(&dirs());
+
+        assert_eq!(handlers(&system_apps, "text/x-cached"), None);
+        assert_eq!(
+            handlers(&system_apps, "text/plain"),
+            Some("kde-foo.desktop;".to_string())
+        );
+        assert_eq!(
+            handlers(&system_apps, "text/html"),
+            Some("shadowed.desktop;".to_string())
+        );
+
+        Ok(())
+    }
 }
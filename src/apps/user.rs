@@ -1,12 +1,14 @@
 use crate::{
-    common::{mime_types, DesktopHandler, Handleable},
-    config::ConfigFile,
+    common::{
+        current_desktops, mime_types, DesktopHandler, Handleable, IniDocument,
+    },
+    config::{ConfigFile, Trace},
     error::{Error, Result},
 };
 use derive_more::{Deref, DerefMut};
 use itertools::Itertools;
 use mime::Mime;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_with::{
     serde_as, DeserializeFromStr, DisplayFromStr, SerializeDisplay,
 };
@@ -14,26 +16,45 @@ use std::{
     collections::{BTreeMap, VecDeque},
     fmt::Display,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use wildmatch::WildMatch;
 
+/// Names of the groups in mimeapps.list
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+
 /// Represents user-configured mimeapps.list file
 #[serde_as]
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 // IMPORTANT: This ensures missing fields are replaced by a default value rather than making deserialization fail entirely
 #[serde(default)]
 pub struct MimeApps {
     #[serde(rename = "Added Associations")]
-    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
     pub added_associations: BTreeMap<Mime, DesktopList>,
     #[serde(rename = "Default Applications")]
-    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
     pub default_apps: BTreeMap<Mime, DesktopList>,
+    #[serde(rename = "Removed Associations")]
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    pub removed_associations: BTreeMap<Mime, DesktopList>,
+    /// The file as it was read, so it can be edited without losing anything handlr does not understand
+    #[serde(skip)]
+    document: IniDocument,
+    /// Path of the file the associations were read from
+    #[serde(skip)]
+    path: PathBuf,
+    /// The other mimeapps.list files in the lookup chain, in order of precedence
+    /// These are only ever read from, changes are only saved to the user's mimeapps.list
+    #[serde(skip)]
+    inherited: Vec<MimeApps>,
+    /// The number of inherited files that take precedence over the user's mimeapps.list
+    #[serde(skip)]
+    overrides: usize,
 }
 
 /// Helper struct for a list of `DesktopHandler`s
@@ -216,12 +237,136 @@ impl MimeApps {
         );
     }
 
+    /// Get every mimeapps.list in the lookup chain, in order of precedence
+    fn layers(&self) -> impl Iterator<Item = &MimeApps> {
+        let (overrides, fallbacks) = self.inherited.split_at(self.overrides);
+        overrides
+            .iter()
+            .chain(std::iter::once(self))
+            .chain(fallbacks.iter())
+    }
+
+    /// Get a human-readable name for the file the associations were read from
+    pub fn source(&self) -> String {
+        if self.path.as_os_str().is_empty() {
+            "mimeapps.list".to_string()
+        } else {
+            self.path.to_string_lossy().to_string()
+        }
+    }
+
+    /// Get the added associations for a given mime from every mimeapps.list in the lookup chain
+    /// Handlers removed by a file are excluded from the added associations of files with lower precedence
+    pub fn get_added_associations(&self, mime: &Mime) -> Option<DesktopList> {
+        let mut removed = DesktopList::default();
+        let mut handlers = DesktopList::default();
+
+        for layer in self.layers() {
+            layer
+                .added_associations
+                .get(mime)
+                .into_iter()
+                .flat_map(|list| list.iter())
+                .filter(|h| !removed.contains(h) && !handlers.contains(h))
+                .cloned()
+                .collect_vec()
+                .into_iter()
+                .for_each(|h| handlers.push_back(h));
+
+            removed.extend(layer.get_removed_from_layer(mime));
+        }
+
+        (!handlers.is_empty()).then_some(handlers)
+    }
+
+    /// Get every handler whose association with a given mime has been removed
+    /// by any mimeapps.list in the lookup chain
+    pub fn get_removed_associations(&self, mime: &Mime) -> DesktopList {
+        DesktopList(
+            self.layers()
+                .flat_map(|layer| layer.get_removed_from_layer(mime))
+                .unique()
+                .collect(),
+        )
+    }
+
+    /// Get the handlers whose association with a given mime has been removed by this mimeapps.list
+    /// Wildcards are expanded, so an entry for `text/*` also applies to `text/plain`
+    fn get_removed_from_layer(
+        &self,
+        mime: &Mime,
+    ) -> impl Iterator<Item = DesktopHandler> + '_ {
+        let mime = mime.clone();
+        self.removed_associations
+            .iter()
+            .filter(move |(m, _)| {
+                WildMatch::new(m.as_ref()).matches(mime.as_ref())
+            })
+            .flat_map(|(_, handlers)| handlers.iter().cloned())
+    }
+
+    /// Add a handler to a given mime's removed associations
+    pub fn add_removed_association(
+        &mut self,
+        mime: &Mime,
+        handler: &DesktopHandler,
+    ) {
+        let handlers =
+            self.removed_associations.entry(mime.clone()).or_default();
+
+        if !handlers.contains(handler) {
+            handlers.push_back(handler.clone());
+        }
+
+        debug!(
+            "New removed associations for `{}`: {}",
+            mime, self.removed_associations[mime]
+        );
+    }
+
+    /// Remove a handler from a given mime's removed associations
+    pub fn remove_removed_association(
+        &mut self,
+        mime: &Mime,
+        handler: &DesktopHandler,
+    ) -> Option<()> {
+        let handlers = self.removed_associations.get_mut(mime)?;
+        let pos = handlers.iter().position(|h| h == handler)?;
+        handlers.remove(pos);
+
+        if handlers.is_empty() {
+            self.removed_associations.remove(mime);
+        }
+
+        debug!(
+            "Removed `{}` from removed associations of `{}`",
+            handler, mime
+        );
+        Some(())
+    }
+
+    /// Get the associations of every mimeapps.list in the lookup chain, along with where they came from
+    pub fn sourced_associations(
+        &self,
+    ) -> impl Iterator<Item = (String, &MimeApps)> {
+        self.layers().map(|layer| (layer.source(), layer))
+    }
+
+    /// Get every wildcard association matching a given mime from this mimeapps.list's default apps
+    pub fn wildcard_candidates(
+        &self,
+        mime: &Mime,
+    ) -> impl Iterator<Item = (&Mime, &DesktopList)> + Clone {
+        let mime = mime.clone();
+        self.default_apps.iter().filter(move |(m, _)| {
+            wildmatch::WildMatch::new(m.as_ref()).matches(mime.as_ref())
+        })
+    }
+
     /// Get a list of handlers associated with a wildcard mime
-    fn get_from_wildcard(&self, mime: &Mime) -> Option<&DesktopList> {
+    pub fn get_from_wildcard(&self, mime: &Mime) -> Option<&DesktopList> {
         // Get the handlers that wildcard match the given mime
-        let associations = self.default_apps.iter().filter(|(m, _)| {
-            wildmatch::WildMatch::new(m.as_ref()).matches(mime.as_ref())
-        });
+        let associations = self.wildcard_candidates(mime);
 
         // Get the length of the longest wildcard that matches
         // Assuming the longest match is the best match
@@ -242,14 +387,89 @@ impl MimeApps {
             .cloned()
     }
 
+    /// Get the default handlers for a given mime from the first mimeapps.list in the lookup chain that sets any
+    pub fn get_default_handlers(&self, mime: &Mime) -> Option<&DesktopList> {
+        self.layers().find_map(|layer| {
+            layer
+                .default_apps
+                .get(mime)
+                .or_else(|| layer.get_from_wildcard(mime))
+                .filter(|handlers| !handlers.is_empty())
+        })
+    }
+
     /// Get the handler associated with a given mime from mimeapps.list's default apps
     #[mutants::skip] // Cannot entirely test, namely cannot test selector or filtering and associated logging
     pub fn get_handler_from_user(
         &self,
         mime: &Mime,
         config_file: &ConfigFile,
+        trace: &mut Trace,
+    ) -> Result<DesktopHandler> {
+        for layer in self.layers() {
+            match layer.get_handler_from_layer(mime, config_file, trace) {
+                Err(Error::NotFound(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(Error::NotFound(mime.to_string()))
+    }
+
+    /// Get the valid handlers associated with a given mime from mimeapps.list's default apps,
+    /// along with their names, from the first mimeapps.list in the lookup chain that has any
+    pub fn get_handlers_from_user(
+        &self,
+        mime: &Mime,
+    ) -> Result<Vec<(DesktopHandler, String)>> {
+        self.layers()
+            .find_map(|layer| {
+                layer
+                    .get_handlers_from_layer(mime, &mut Trace::default())
+                    .ok()
+                    .filter(|handlers| !handlers.is_empty())
+            })
+            .ok_or_else(|| Error::NotFound(mime.to_string()))
+    }
+
+    /// Get the handler associated with a given mime from a single mimeapps.list's default apps
+    #[mutants::skip] // Cannot entirely test, namely cannot test selector or filtering and associated logging
+    fn get_handler_from_layer(
+        &self,
+        mime: &Mime,
+        config_file: &ConfigFile,
+        trace: &mut Trace,
     ) -> Result<DesktopHandler> {
         let error = Error::NotFound(mime.to_string());
+        let handlers = self.get_handlers_from_layer(mime, trace)?;
+        let source = format!("default apps in {}", self.source());
+
+        debug!(
+            "Selector enabled: {}, number of set handlers: {}",
+            config_file.enable_selector,
+            handlers.len()
+        );
+        if let Some((first, _)) = handlers.first() {
+            trace.choose(first, source);
+        }
+
+        if config_file.enable_selector && handlers.len() > 1 {
+            info!("Running selector: {}", &config_file.selector);
+            trace.select(&config_file.selector, handlers)
+        } else {
+            info!("Not running selector, choosing first handler");
+            Ok(handlers.first().ok_or(error)?.0.clone())
+        }
+    }
+
+    /// Get the valid handlers associated with a given mime from a single mimeapps.list's default apps,
+    /// along with their names
+    #[mutants::skip] // Cannot entirely test, namely cannot test filtering and associated logging
+    fn get_handlers_from_layer(
+        &self,
+        mime: &Mime,
+        trace: &mut Trace,
+    ) -> Result<Vec<(DesktopHandler, String)>> {
         // Check for an exact match first and then fall back to wildcard
         match self
             .default_apps
@@ -258,18 +478,20 @@ impl MimeApps {
         {
             Some(handlers) => {
                 debug!(
-                    "Configured handlers for `{}` in mimeapps.list Default Associations: {}",
-                    mime, handlers
+                    "Configured handlers for `{}` in {} Default Associations: {}",
+                    mime,
+                    self.source(),
+                    handlers
                 );
                 // Prepares for selector and filters out apps that do not exist
-                let handlers = handlers
+                Ok(handlers
                     .iter()
-                    .flat_map(|h| -> Result<(&DesktopHandler, String)> {
-                        // Filtering breaks testing, so treat every app as valid
+                    .flat_map(|h| -> Result<(DesktopHandler, String)> {
+                        // Filtering breaks testing with made-up apps, so treat those as valid
                         // TODO: test logging
 
-                        if cfg!(test) {
-                            Ok((h, h.to_string()))
+                        if cfg!(test) && !Path::new(&h.to_string()).exists() {
+                            Ok((h.clone(), h.to_string()))
                         } else {
                             let entry = h.get_entry();
                             if let Err(ref e) = entry {
@@ -277,45 +499,23 @@ impl MimeApps {
                                     "Desktop entry `{}` is invalid: {}",
                                     h, e
                                 );
+                                trace.skip(h, e);
                             } else {
                                 debug!("Desktop entry `{}` is valid", h);
                             }
 
-                            Ok((h, entry?.name))
+                            Ok((h.clone(), entry?.name))
                         }
                     })
-                    .collect_vec();
-
-                debug!(
-                    "Selector enabled: {}, number of set handlers: {}",
-                    config_file.enable_selector,
-                    handlers.len()
-                );
-                if config_file.enable_selector && handlers.len() > 1 {
-                    info!("Running selector: {}", &config_file.selector);
-                    let handler = {
-                        let name = select(
-                            &config_file.selector,
-                            handlers.iter().map(|h| h.1.clone()),
-                        )?;
-
-                        handlers
-                            .into_iter()
-                            .find(|h| h.1 == name)
-                            .ok_or(error)?
-                            .0
-                            .clone()
-                    };
-
-                    Ok(handler)
-                } else {
-                    info!("Not running selector, choosing first handler");
-                    Ok(handlers.first().ok_or(error)?.0.clone())
-                }
+                    .collect_vec())
             }
             None => {
-                info!("No handlers configured for `{}` in mimeapps.list Default associations", mime);
-                Err(error)
+                info!(
+                    "No handlers configured for `{}` in {} Default associations",
+                    mime,
+                    self.source()
+                );
+                Err(Error::NotFound(mime.to_string()))
             }
         }
     }
@@ -328,29 +528,103 @@ impl MimeApps {
         Ok(config)
     }
 
-    /// Read and parse mimeapps.list
+    /// Get the paths of every mimeapps.list in the lookup chain, in order of precedence
+    /// See <https://specifications.freedesktop.org/mime-apps-spec/latest/file.html>
+    #[mutants::skip] // Cannot test directly, depends on system state
+    fn paths() -> Result<Vec<PathBuf>> {
+        let base_dirs = xdg::BaseDirectories::new()?;
+        let desktops = current_desktops()
+            .iter()
+            .map(|desktop| desktop.to_lowercase())
+            .collect_vec();
+
+        Ok(std::iter::once(base_dirs.get_config_home())
+            .chain(base_dirs.get_config_dirs())
+            .chain(
+                std::iter::once(base_dirs.get_data_home())
+                    .chain(base_dirs.get_data_dirs())
+                    .map(|dir| dir.join("applications")),
+            )
+            .flat_map(|dir| {
+                desktops
+                    .iter()
+                    .map(|desktop| dir.join(format!("{desktop}-mimeapps.list")))
+                    .chain(std::iter::once(dir.join("mimeapps.list")))
+                    .collect_vec()
+            })
+            .unique()
+            .collect_vec())
+    }
+
+    /// Read and parse mimeapps.list along with the rest of the lookup chain
     #[mutants::skip] // Cannot test directly, depends on system state
     pub fn read() -> Result<Self> {
-        let exists = std::path::Path::new(&Self::path()?).exists();
+        let path = Self::path()?;
+        let exists = path.exists();
 
         let file = std::fs::OpenOptions::new()
             .write(!exists)
             .create(!exists)
             .read(true)
-            .open(Self::path()?)?;
+            .open(&path)?;
 
-        Self::read_from(file)
+        let mut mime_apps = Self::read_from(file)?;
+        mime_apps.path = path;
+        mime_apps.inherit(Self::paths()?);
+
+        Ok(mime_apps)
+    }
+
+    /// Read the other mimeapps.list files in the lookup chain, given every path in it in order of precedence
+    /// Files that do not exist are skipped and files that cannot be parsed are skipped with a warning
+    fn inherit<I: IntoIterator<Item = PathBuf>>(&mut self, paths: I) {
+        let mut overrides_user = true;
+
+        for path in paths {
+            if path == self.path {
+                overrides_user = false;
+                continue;
+            }
+
+            if !path.exists() {
+                continue;
+            }
+
+            match std::fs::File::open(&path)
+                .map_err(Error::from)
+                .and_then(Self::read_from)
+            {
+                Ok(mut mime_apps) => {
+                    debug!("Inheriting associations from {}", path.display());
+                    mime_apps.path = path;
+                    self.inherited.push(mime_apps);
+                    if overrides_user {
+                        self.overrides += 1;
+                    }
+                }
+                Err(e) => {
+                    warn!("Could not read {}: {}", path.display(), e)
+                }
+            }
+        }
     }
 
     /// Deserialize MimeApps from reader
     /// Makes testing easier
-    fn read_from<R: Read>(reader: R) -> Result<Self> {
-        let mut mime_apps: MimeApps = serde_ini::de::from_read(reader)?;
+    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut mime_apps: MimeApps = serde_ini::de::from_str(&text)?;
+        mime_apps.document = IniDocument::parse(&text);
 
         // Remove empty entries
         mime_apps
             .default_apps
             .retain(|_, handlers| !handlers.is_empty());
+        mime_apps
+            .removed_associations
+            .retain(|_, handlers| !handlers.is_empty());
 
         Ok(mime_apps)
     }
@@ -372,30 +646,62 @@ impl MimeApps {
         }
     }
 
-    /// Serialize MimeApps and write to writer
+    /// Write MimeApps to writer
+    /// Only entries that have changed since being read are rewritten,
+    /// so comments, unknown groups, key order, etc. are preserved
     /// Makes testing easier
     fn save_to<W: Write>(&mut self, writer: &mut W) -> Result<()> {
         // Remove empty entries
         self.default_apps.retain(|_, handlers| !handlers.is_empty());
+        self.removed_associations
+            .retain(|_, handlers| !handlers.is_empty());
 
-        // Use Linefeed instead of default carriage return
-        let w = serde_ini::write::Writer::new(
-            writer,
-            serde_ini::write::LineEnding::Linefeed,
-        );
-        let mut ser = serde_ini::ser::Serializer::new(w);
-        self.serialize(&mut ser)?;
+        for (group, associations) in [
+            (ADDED_ASSOCIATIONS, &self.added_associations),
+            (DEFAULT_APPLICATIONS, &self.default_apps),
+            (REMOVED_ASSOCIATIONS, &self.removed_associations),
+        ] {
+            // Remove entries that no longer exist
+            // Keys that are not valid mimes are left alone
+            self.document
+                .entries(group)
+                .into_iter()
+                .filter(|(key, _)| {
+                    Mime::from_str(key)
+                        .is_ok_and(|mime| !associations.contains_key(&mime))
+                })
+                .map(|(key, _)| key.to_owned())
+                .collect_vec()
+                .into_iter()
+                .for_each(|key| self.document.remove(group, &key));
+
+            // Rewrite entries that have changed and add new ones
+            associations.iter().for_each(|(mime, handlers)| {
+                let unchanged = self
+                    .document
+                    .get(group, mime.as_ref())
+                    .and_then(|value| DesktopList::from_str(value).ok())
+                    .is_some_and(|value| value == *handlers);
+
+                if !unchanged {
+                    self.document.set(
+                        group,
+                        mime.as_ref(),
+                        &handlers.to_string(),
+                    );
+                }
+            });
+        }
+
+        write!(writer, "{}", self.document)?;
 
         Ok(())
     }
 }
 
-/// Run given selector command
+/// Run given selector command to choose between the given handlers, listed by name
 #[mutants::skip] // Cannot test directly, runs external command
-fn select<O: Iterator<Item = String>>(
-    selector: &str,
-    mut opts: O,
-) -> Result<String> {
+pub fn select<H>(selector: &str, handlers: Vec<(H, String)>) -> Result<H> {
     use std::{
         io::prelude::*,
         process::{Command, Stdio},
@@ -416,7 +722,7 @@ fn select<O: Iterator<Item = String>>(
         process
             .stdin
             .ok_or_else(|| Error::Selector(selector.to_string()))?
-            .write_all(opts.join("\n").as_bytes())?;
+            .write_all(handlers.iter().map(|h| &h.1).join("\n").as_bytes())?;
 
         let mut output = String::with_capacity(24);
 
@@ -431,7 +737,11 @@ fn select<O: Iterator<Item = String>>(
     if output.is_empty() {
         Err(Error::Cancelled)
     } else {
-        Ok(output)
+        handlers
+            .into_iter()
+            .find(|h| h.1 == output)
+            .map(|h| h.0)
+            .ok_or(Error::NotFound(output))
     }
 }
 
@@ -490,10 +800,9 @@ mod tests {
 
     #[test]
     fn mimeapps_anomalous_semicolons_round_trip() -> Result<()> {
-        mimeapps_round_trip(
+        // Untouched entries are written back as-is
+        mimeapps_round_trip_simple(
             "./tests/assets/mimeapps_anomalous_semicolons.list",
-            "./tests/assets/mimeapps_sorted.list",
-            noop,
         )
     }
 
@@ -514,7 +823,11 @@ mod tests {
 
         assert_eq!(
             mime_apps
-                .get_handler_from_user(&mime::TEXT_PLAIN, &config_file)?
+                .get_handler_from_user(
+                    &mime::TEXT_PLAIN,
+                    &config_file,
+                    &mut Trace::default()
+                )?
                 .to_string(),
             "nvim.desktop"
         );
@@ -522,6 +835,163 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mimeapps_lookup_chain() -> Result<()> {
+        let user_path = PathBuf::from("./tests/assets/mimeapps_no_added.list");
+        let mut mime_apps = MimeApps::read_from(File::open(&user_path)?)?;
+        mime_apps.path = user_path.clone();
+
+        mime_apps.inherit(
+            [
+                "./tests/assets/sway-mimeapps.list",
+                "./tests/assets/mimeapps_no_added.list",
+                "./tests/assets/nonexistent-mimeapps.list",
+                "./tests/assets/mimeapps_sorted.list",
+            ]
+            .map(PathBuf::from),
+        );
+
+        assert_eq!(mime_apps.overrides, 1);
+        assert_eq!(
+            mime_apps.layers().map(|layer| layer.source()).collect_vec(),
+            [
+                "./tests/assets/sway-mimeapps.list",
+                "./tests/assets/mimeapps_no_added.list",
+                "./tests/assets/mimeapps_sorted.list",
+            ]
+        );
+
+        let config_file = ConfigFile::default();
+        let get_handler = |mime: &str| -> Result<String> {
+            Ok(mime_apps
+                .get_handler_from_user(
+                    &Mime::from_str(mime)?,
+                    &config_file,
+                    &mut Trace::default(),
+                )?
+                .to_string())
+        };
+
+        // Desktop-specific file takes precedence over the user's mimeapps.list
+        assert_eq!(get_handler("text/plain")?, "Helix.desktop");
+        // User's wildcard takes precedence over an exact match in a lower file
+        assert_eq!(get_handler("text/html")?, "nvim.desktop");
+        // Fall back to lower files when nothing matches
+        assert_eq!(get_handler("image/png")?, "imv.desktop");
+        assert!(get_handler("application/x-nonexistent").is_err());
+
+        assert_eq!(
+            mime_apps
+                .get_added_associations(&Mime::from_str(
+                    "x-scheme-handler/terminal"
+                )?)
+                .map(|handlers| handlers.to_string()),
+            Some(
+                "org.wezfurlong.wezterm.desktop;org.codeberg.dnkl.foot.desktop;"
+                    .to_string()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimeapps_removed_round_trip() -> Result<()> {
+        mimeapps_round_trip_simple("./tests/assets/mimeapps_removed.list")
+    }
+
+    #[test]
+    fn mimeapps_comments_round_trip() -> Result<()> {
+        mimeapps_round_trip_simple("./tests/assets/mimeapps_comments.list")
+    }
+
+    #[test]
+    fn mimeapps_comments_edit() -> Result<()> {
+        fn edit(mime_apps: &mut MimeApps) -> Result<()> {
+            let text = Mime::from_str("text/plain")?;
+
+            mime_apps.set_handler(
+                &text,
+                &DesktopHandler::assume_valid("Helix.desktop".into()),
+                false,
+            )?;
+            mime_apps.set_handler(
+                &Mime::from_str("video/mp4")?,
+                &DesktopHandler::assume_valid("mpv.desktop".into()),
+                false,
+            )?;
+            mime_apps.unset_handler(&Mime::from_str("image/png")?);
+            mime_apps.add_removed_association(
+                &text,
+                &DesktopHandler::assume_valid("nvim.desktop".into()),
+            );
+            Ok(())
+        }
+
+        // Only the touched lines change
+        mimeapps_round_trip(
+            "./tests/assets/mimeapps_comments.list",
+            "./tests/assets/mimeapps_comments_edited.list",
+            edit,
+        )
+    }
+
+    #[test]
+    fn removed_associations() -> Result<()> {
+        let user_path = PathBuf::from("./tests/assets/mimeapps_removed.list");
+        let mut mime_apps = MimeApps::read_from(File::open(&user_path)?)?;
+        mime_apps.path = user_path.clone();
+        mime_apps.inherit([
+            user_path,
+            PathBuf::from("./tests/assets/mimeapps_sorted.list"),
+        ]);
+
+        let terminal = Mime::from_str("x-scheme-handler/terminal")?;
+        let wezterm = DesktopHandler::assume_valid(
+            "org.wezfurlong.wezterm.desktop".into(),
+        );
+
+        // Removed by the user's mimeapps.list, so only foot is left from the lower file
+        assert_eq!(
+            mime_apps
+                .get_added_associations(&terminal)
+                .map(|handlers| handlers.to_string()),
+            Some("org.codeberg.dnkl.foot.desktop;".to_string())
+        );
+        assert!(mime_apps
+            .get_removed_associations(&terminal)
+            .contains(&wezterm));
+
+        // Wildcards in removed associations apply to matching mimes
+        assert!(mime_apps
+            .get_removed_associations(&mime::TEXT_PLAIN)
+            .contains(&DesktopHandler::assume_valid("nvim.desktop".into())));
+
+        mime_apps.remove_removed_association(&terminal, &wezterm);
+        assert_eq!(
+            mime_apps
+                .get_added_associations(&terminal)
+                .map(|handlers| handlers.to_string()),
+            Some(
+                "org.wezfurlong.wezterm.desktop;org.codeberg.dnkl.foot.desktop;"
+                    .to_string()
+            )
+        );
+
+        mime_apps.add_removed_association(&terminal, &wezterm);
+        // Adding the same association twice should not duplicate it
+        mime_apps.add_removed_association(&terminal, &wezterm);
+
+        let mut buffer = Vec::new();
+        mime_apps.save_to(&mut buffer)?;
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            std::fs::read_to_string("./tests/assets/mimeapps_removed.list")?
+        );
+
+        Ok(())
+    }
+
     #[test]
     // This is mainly to check that "empty" entries don't get mixed in and complicate things
     fn mimeapps_round_trip_with_deletion_and_re_addition() -> Result<()> {
@@ -545,11 +1015,8 @@ mod tests {
 
     #[test]
     fn mimeapps_duplicate_round_trip() -> Result<()> {
-        mimeapps_round_trip(
-            "./tests/assets/mimeapps_duplicate.list",
-            "./tests/assets/mimeapps_no_added.list",
-            noop,
-        )
+        // Untouched entries are written back as-is
+        mimeapps_round_trip_simple("./tests/assets/mimeapps_duplicate.list")
     }
 
     #[test]
@@ -10,7 +10,8 @@ use clap_verbosity_flag::{Verbosity, WarnLevel};
 use crate::{
     apps::SystemApps,
     common::mime_types,
-    common::{DesktopHandler, MimeType, UserPath},
+    common::{DesktopHandler, MimeOrPath, MimeType, UserPath},
+    config::ConfigFile,
 };
 #[cfg(executable)]
 use clap::builder::StyledStr;
@@ -26,6 +27,8 @@ pub type DesktopHandler = String;
 pub type MimeType = String;
 #[cfg(not(executable))]
 pub type UserPath = String;
+#[cfg(not(executable))]
+pub type MimeOrPath = String;
 
 /// A better xdg-utils
 ///
@@ -37,6 +40,8 @@ pub type UserPath = String;
 ///
 /// Mime associations file location: $XDG_CONFIG_HOME/mimeapps.list
 ///
+/// Other mimeapps.list files in the XDG lookup chain (e.g. /etc/xdg/mimeapps.list) are also read, but never written to
+///
 /// Config file location: $XDG_CONFIG_HOME/handlr/handlr.toml
 ///
 /// Log file location: $XDG_CACHE_HOME/handlr/handlr.log
@@ -80,7 +85,7 @@ pub enum Cmd {
     /// Output is formatted as a table with two columns.
     /// The left column shows mimetypes and the right column shows the handlers
     ///
-    /// Currently does not support regex handlers.
+    /// Regex handlers are only shown with `--all`.
     ///
     /// When using `--json`, output will be in the form:
     ///
@@ -106,16 +111,30 @@ pub enum Cmd {
     /// {
     ///   "added_associations": [ ... ],   
     ///   "default_apps": [ ... ],
-    ///   "system_apps": [ ... ]
+    ///   "removed_associations": [ ... ],
+    ///   "system_apps": [ ... ],
+    ///   "regex_handlers": [
+    ///     {
+    ///       "exec": "freetube %u",
+    ///       "terminal": false,
+    ///       "regexes": [
+    ///         "(https://)?(www\.)?youtu(be\.com|\.be)/*"
+    ///       ]
+    ///     },
+    ///     ...
+    ///   ]
     /// }
     ///
-    /// Where each top-level key has an array with the same scheme as the normal `--json` output
+    /// Where each top-level key except "regex_handlers" has an array with the same scheme as the normal `--json` output,
+    /// except that entries in "added_associations" and "default_apps" also have a "source" key
+    /// with the path of the mimeapps.list they came from
     #[clap(verbatim_doc_comment)]
     List {
         /// Output handler info as json
         #[clap(long)]
         json: bool,
-        /// Expand wildcards in mimetypes and show global defaults
+        /// Expand wildcards in mimetypes and show global defaults,
+        /// including associations from every mimeapps.list in the lookup chain
         #[clap(long, short)]
         all: bool,
     },
@@ -132,6 +151,12 @@ pub enum Cmd {
         #[clap(required = true)]
         #[cfg_attr(executable, clap(add = ArgValueCompleter::new(PathCompleter::any())))]
         paths: Vec<UserPath>,
+        /// Print the commands that would be run instead of running them
+        ///
+        /// Each command is printed on its own line, quoted like in a shell,
+        /// in the order of the first path opened by each handler.
+        #[clap(long)]
+        dry_run: bool,
         #[command(flatten)]
         selector_args: SelectorArgs,
     },
@@ -146,7 +171,7 @@ pub enum Cmd {
     ///
     /// File extensions are converted into their respective mimetypes in mimeapps.list.
     ///
-    /// Currently does not support regex handlers.
+    /// To set regex handlers, use `handlr regex set` instead.
     Set {
         /// Mimetype or file extension to operate on.
         #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_mimes)))]
@@ -163,7 +188,7 @@ pub enum Cmd {
     ///
     /// If multiple default handlers are set, both will be removed.
     ///
-    /// Currently does not support regex handlers.
+    /// To remove regex handlers, use `handlr regex remove` instead.
     Unset {
         /// Mimetype or file extension to unset the default handler of
         #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_mimes)))]
@@ -185,17 +210,22 @@ pub enum Cmd {
         // Not necessarily a path, but completing as a path tends to be the expected "default" behavior
         #[clap(add=ArgValueCompleter::new(PathCompleter::any()))]
         args: Vec<String>,
+        /// Print the command that would be run instead of running it
+        #[clap(long)]
+        dry_run: bool,
         #[command(flatten)]
         selector_args: SelectorArgs,
     },
 
-    /// Get handler for this mime/extension
+    /// Get handler for this mime/extension or path/URL
     ///
     /// If multiple handlers are set and `enable_selector` is set to true,
     /// you will be prompted to select one using `selector` from ~/.config/handlr/handlr.toml.
     /// Otherwise, only the default handler will be printed.
     ///
-    /// Note that regex handlers are not supported by this subcommand currently.
+    /// Regex handlers are taken into account for paths/URLs.
+    /// URLs and existing files are treated as paths.
+    /// Otherwise, the argument is treated as a mimetype or file extension if it is a valid one, or a path if not.
     ///
     /// When using `--json`, output is in the form:
     ///
@@ -212,13 +242,60 @@ pub enum Cmd {
         /// Output handler info as json
         #[clap(long)]
         json: bool,
-        /// Mimetype to get the handler of
+        /// Mimetype/file extension or path/URL to get the handler of
         #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_mimes)))]
-        mime: MimeType,
+        target: MimeOrPath,
         #[command(flatten)]
         selector_args: SelectorArgs,
     },
 
+    /// Explain how the handler for a path/URL or mime/extension is chosen
+    ///
+    /// Shows the detected mimetype and how it was detected, the regex handlers that were tested,
+    /// the default apps in every mimeapps.list in the lookup chain (including matching wildcards),
+    /// the added and removed associations, the installed applications that support the mimetype,
+    /// any handlers that were skipped and why, and finally the handler that would be used.
+    ///
+    /// URLs and existing files are treated as paths.
+    /// Otherwise, the argument is treated as a mimetype or file extension if it is a valid one, or a path if not.
+    ///
+    /// The selector is never run. If it would be, the first default app is shown as the handler.
+    ///
+    /// When using `--json`, output is in the form:
+    ///
+    /// {
+    ///   "input": "README.md",
+    ///   "mime": "text/markdown",
+    ///   "mime_source": "file_name",
+    ///   "regex_handlers": [ { "handler": "...", "matches": [ ... ] }, ... ],
+    ///   "default_apps": [
+    ///     {
+    ///       "source": "/home/user/.config/mimeapps.list",
+    ///       "exact": null,
+    ///       "wildcards": [ { "mime": "text/*", "length": 6, "handlers": [ "Helix.desktop" ] } ]
+    ///     },
+    ///     ...
+    ///   ],
+    ///   "added_associations": [ ... ],
+    ///   "removed_associations": [ ... ],
+    ///   "system_apps": [ ... ],
+    ///   "skipped": [ { "handler": "...", "reason": "..." }, ... ],
+    ///   "selector": false,
+    ///   "handler": { "handler": "Helix.desktop", "source": "default apps in /home/user/.config/mimeapps.list" }
+    /// }
+    ///
+    /// "mime_source" is one of "given", "extension", "url_scheme", "file_name" or "file_contents",
+    /// and "handler" is null if no handler is found.
+    #[clap(verbatim_doc_comment)]
+    Why {
+        /// Path/URL or mimetype/file extension to explain
+        #[clap(add = ArgValueCompleter::new(PathCompleter::any()))]
+        target: MimeOrPath,
+        /// Output the explanation as json
+        #[clap(long)]
+        json: bool,
+    },
+
     /// Add a handler for given mime/extension
     ///
     /// Note that the first handler is the default.
@@ -252,9 +329,50 @@ pub enum Cmd {
         handler: DesktopHandler,
     },
 
+    /// Manage removed associations in mimeapps.list
+    ///
+    /// Handlers in the `[Removed Associations]` group of mimeapps.list will never be chosen
+    /// from added associations or installed applications for the given mime/extension.
+    ///
+    /// Asterisks can be used as wildcards to apply to multiple mimetypes.
+    Removed {
+        #[command(subcommand)]
+        command: RemovedCmd,
+    },
+
+    /// Manage regex handlers in handlr.toml
+    ///
+    /// Regex handlers are arbitrary commands used to open paths/URLs that match any of their regular expressions.
+    /// They take precedence over handlers for mimetypes, and are identified by their command.
+    ///
+    /// Changes are written to ~/.config/handlr/handlr.toml, keeping its comments and formatting.
+    Regex {
+        #[command(subcommand)]
+        command: RegexCmd,
+    },
+
+    /// Get or set values in handlr.toml
+    ///
+    /// Changes are written to ~/.config/handlr/handlr.toml, keeping its comments and formatting.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCmd,
+    },
+
+    /// Manage the cache of installed applications
+    ///
+    /// Installed desktop entries are cached in `$XDG_CACHE_HOME/handlr` so they do not have to be parsed every time handlr is run.
+    /// The cache is automatically rebuilt whenever desktop entries are added, removed or modified.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCmd,
+    },
+
     /// Get the mimetype of a given file/URL
     ///
     /// By default, output is in the form of a table that matches file paths/URLs to their mimetypes.
+    /// If any paths/URLs are changed by rewrite rules, what they are rewritten to is shown in an extra column,
+    /// and the mimetype is that of the rewritten path/URL.
     ///
     /// When using `--json`, output will be in the form:
     ///
@@ -264,11 +382,14 @@ pub enum Cmd {
     ///     "mime": "text/markdown"
     ///   },
     ///   {
-    ///     "path": "https://duckduckgo.com/"
+    ///     "path": "https://www.reddit.com/r/rust"
+    ///     "rewritten": "https://old.reddit.com/r/rust"
     ///     "mime": "x-scheme-handler/https"
     ///   },
     /// ...
     /// ]
+    ///
+    /// Where "rewritten" is only present for paths/URLs that are changed by rewrite rules
     #[clap(verbatim_doc_comment)]
     Mime {
         /// File paths/URLs to get the mimetype of
@@ -280,6 +401,112 @@ pub enum Cmd {
     },
 }
 
+#[deny(missing_docs)]
+#[derive(Clone, Subcommand)]
+pub enum RemovedCmd {
+    /// Add a removed association for a given mime/extension
+    Add {
+        /// Mimetype or file extension to remove the association from
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_mimes)))]
+        mime: MimeType,
+        /// Desktop file of handler program
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_desktop_files)))]
+        handler: DesktopHandler,
+    },
+
+    /// Remove a removed association for a given mime/extension, restoring the association
+    Remove {
+        /// Mimetype or file extension to restore the association for
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_mimes)))]
+        mime: MimeType,
+        /// Desktop file of handler program
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_desktop_files)))]
+        handler: DesktopHandler,
+    },
+}
+
+#[deny(missing_docs)]
+#[derive(Clone, Subcommand)]
+pub enum ConfigCmd {
+    /// Print the value of a key, or the whole config if no key is given
+    ///
+    /// Defaults are shown for keys that are not set in handlr.toml.
+    /// Strings are printed as-is, while everything else is printed as TOML.
+    Get {
+        /// Key to get the value of
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_config_keys)))]
+        key: Option<String>,
+    },
+
+    /// Set the value of a key
+    ///
+    /// The value is parsed as TOML (e.g. `true`, `500`, `"string"`, `[1, 2]`).
+    /// If it is not valid TOML, it is treated as a string.
+    Set {
+        /// Key to set the value of
+        #[cfg_attr(executable, clap(add = ArgValueCompleter::new(autocomplete_config_keys)))]
+        key: String,
+        /// New value
+        value: String,
+    },
+}
+
+#[deny(missing_docs)]
+#[derive(Clone, Subcommand)]
+pub enum RegexCmd {
+    /// Set the regexes of a regex handler, overwriting any existing ones
+    ///
+    /// The handler is created if there is none with the given command.
+    Set {
+        /// Command to run, using desktop entry field codes (e.g. `freetube %u`) and capture groups (e.g. `{id}`)
+        exec: String,
+        /// Regular expressions matching the paths/URLs to open with the command
+        #[clap(required = true)]
+        regexes: Vec<String>,
+        /// Run the command in a terminal
+        #[clap(long)]
+        terminal: bool,
+        /// Priority of the handler, where handlers with a higher priority are tried first
+        #[clap(long, allow_negative_numbers = true)]
+        priority: Option<i64>,
+    },
+
+    /// Add regexes to a regex handler, keeping any existing ones
+    ///
+    /// The handler is created if there is none with the given command.
+    /// Its terminal flag and priority are only changed if they are given.
+    Add {
+        /// Command to run, using desktop entry field codes (e.g. `freetube %u`) and capture groups (e.g. `{id}`)
+        exec: String,
+        /// Regular expressions matching the paths/URLs to open with the command
+        #[clap(required = true)]
+        regexes: Vec<String>,
+        /// Whether to run the command in a terminal, keeping the current setting if not given
+        #[clap(long)]
+        terminal: Option<bool>,
+        /// Priority of the handler, where handlers with a higher priority are tried first
+        #[clap(long, allow_negative_numbers = true)]
+        priority: Option<i64>,
+    },
+
+    /// Remove regexes from a regex handler
+    ///
+    /// If no regexes are given, or it has no regexes or globs left, the whole handler is removed.
+    Remove {
+        /// Command of the regex handler
+        exec: String,
+        /// Regular expressions to remove
+        regexes: Vec<String>,
+    },
+}
+
+#[deny(missing_docs)]
+#[derive(Clone, Subcommand)]
+pub enum CacheCmd {
+    /// Rebuild the cache of installed applications from scratch
+    Rebuild,
+}
+
 #[derive(Clone, Args)]
 pub struct SelectorArgs {
     /// Override the configured selector command
@@ -304,21 +531,52 @@ fn autocomplete_mimes(current: &OsStr) -> Vec<CompletionCandidate> {
     mimes
 }
 
+/// Generate candidates for keys in handlr.toml
+#[cfg(executable)]
+fn autocomplete_config_keys(current: &OsStr) -> Vec<CompletionCandidate> {
+    ConfigFile::keys()
+        .expect("handlr error: Could not get config keys")
+        .into_iter()
+        .filter(|key| key.starts_with(current.to_string_lossy().as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
 /// Generate candidates for desktop files
+/// Once a desktop file has been completed, its actions are suggested too
 #[mutants::skip] // Cannot test directly, relies on system state
 #[cfg(executable)]
 fn autocomplete_desktop_files(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+
     SystemApps::get_entries()
         .expect("handlr error: Could not get system desktop entries")
+        .filter(|(_, entry)| !entry.no_display && entry.is_available())
+        .flat_map(|(path, entry)| {
+            let path = path.to_string_lossy().to_string();
+            let actions = entry
+                .actions
+                .iter()
+                .map(|action| {
+                    (
+                        format!("{}:{}", path, action.id),
+                        format!("{} ({})", entry.name, action.name),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            std::iter::once((path, entry.name)).chain(actions)
+        })
         .filter(|(path, _)| {
-            path.to_string_lossy()
-                .starts_with(current.to_string_lossy().as_ref())
+            path.starts_with(current.as_ref())
+                // Only suggest actions once the desktop file is complete
+                && (!path.contains(".desktop:") || current.contains(".desktop:"))
         })
-        .map(|(path, entry)| {
-            let mut name = StyledStr::new();
-            write!(name, "{}", entry.name)
+        .map(|(path, name)| {
+            let mut help = StyledStr::new();
+            write!(help, "{}", name)
                 .expect("handlr error: Could not write desktop entry name");
-            CompletionCandidate::new(path).help(Some(name))
+            CompletionCandidate::new(path).help(Some(help))
         })
         .collect()
 }
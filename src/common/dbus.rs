@@ -0,0 +1,72 @@
+use crate::error::Result;
+use std::collections::HashMap;
+use zbus::{blocking::Connection, zvariant::Value};
+
+/// Name of the D-Bus interface implemented by D-Bus activatable applications
+const APPLICATION_INTERFACE: &str = "org.freedesktop.Application";
+
+/// An application that can be activated through D-Bus
+/// See <https://specifications.freedesktop.org/desktop-entry-spec/latest/dbus.html>
+pub struct DBusApplication<'a> {
+    connection: &'a Connection,
+    /// Well-known bus name of the application, i.e. its desktop file ID without `.desktop`
+    app_id: &'a str,
+}
+
+impl<'a> DBusApplication<'a> {
+    /// Create a new DBusApplication, given its desktop file ID without `.desktop`
+    pub fn new(connection: &'a Connection, app_id: &'a str) -> Self {
+        Self { connection, app_id }
+    }
+
+    /// Get the object path of the application
+    /// e.g. `org.example.App-Name` becomes `/org/example/App_Name`
+    fn object_path(&self) -> String {
+        format!("/{}", self.app_id.replace('.', "/").replace('-', "_"))
+    }
+
+    /// Call a method of the `org.freedesktop.Application` interface
+    fn call<B>(&self, method: &str, body: &B) -> Result<()>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        self.connection.call_method(
+            Some(self.app_id),
+            self.object_path(),
+            Some(APPLICATION_INTERFACE),
+            method,
+            body,
+        )?;
+        Ok(())
+    }
+
+    /// Activate the application without opening anything
+    pub fn activate(&self) -> Result<()> {
+        self.call("Activate", &(platform_data(),))
+    }
+
+    /// Open the given URIs with the application
+    pub fn open(&self, uris: &[String]) -> Result<()> {
+        self.call("Open", &(uris, platform_data()))
+    }
+
+    /// Activate one of the application's desktop actions
+    pub fn activate_action(&self, action: &str) -> Result<()> {
+        self.call(
+            "ActivateAction",
+            &(action, Vec::<Value>::new(), platform_data()),
+        )
+    }
+}
+
+/// Get the platform data to pass along with method calls
+/// Forwards any activation token so the application's window can be focused
+fn platform_data() -> HashMap<&'static str, Value<'static>> {
+    ["XDG_ACTIVATION_TOKEN", "DESKTOP_STARTUP_ID"]
+        .into_iter()
+        .zip(["activation-token", "desktop-startup-id"])
+        .filter_map(|(var, key)| {
+            Some((key, Value::from(std::env::var(var).ok()?)))
+        })
+        .collect()
+}
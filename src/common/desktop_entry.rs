@@ -1,8 +1,12 @@
 use crate::{
+    common::{
+        dbus::DBusApplication,
+        exec::{field_codes, split_exec},
+        UserPath,
+    },
     config::Config,
     error::{Error, Result},
 };
-use aho_corasick::AhoCorasick;
 use freedesktop_desktop_entry::{
     get_languages_from_env, DesktopEntry as FreeDesktopEntry,
 };
@@ -10,13 +14,21 @@ use itertools::Itertools;
 use mime::Mime;
 use once_cell::sync::Lazy;
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
-    ffi::OsString,
+    ffi::{OsStr, OsString},
+    os::unix::{
+        fs::PermissionsExt,
+        process::{CommandExt, ExitStatusExt},
+    },
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, Command, ExitStatus, Stdio},
     str::FromStr,
+    time::Duration,
 };
 use tracing::debug;
+use url::Url;
+use zbus::blocking::Connection;
 
 /// Represents a desktop entry file for an application
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -27,12 +39,47 @@ pub struct DesktopEntry {
     pub exec: String,
     /// Name of the desktop entry file
     pub file_name: OsString,
+    /// Location of the desktop entry file
+    pub location: Option<PathBuf>,
+    /// Icon to display for the application
+    pub icon: Option<String>,
     /// Whether the program runs in a terminal window
     pub terminal: bool,
+    /// Working directory to run the program in
+    pub working_dir: Option<PathBuf>,
+    /// Extra environment variables to run the program with
+    pub env: BTreeMap<String, String>,
     /// The MIME type(s) supported by this application
     pub mime_type: Vec<Mime>,
     /// Categories in which the entry should be shown in a menu
     pub categories: Vec<String>,
+    /// Whether the entry has been deleted and should be treated as if it does not exist
+    pub hidden: bool,
+    /// Whether the entry should not be shown in menus
+    pub no_display: bool,
+    /// Program used to check if the application is actually installed
+    pub try_exec: Option<String>,
+    /// Desktop environments the entry should only be shown in
+    pub only_show_in: Vec<String>,
+    /// Desktop environments the entry should not be shown in
+    pub not_show_in: Vec<String>,
+    /// Additional actions the application supports
+    pub actions: Vec<DesktopAction>,
+    /// The action to run instead of the main command, if any
+    pub action: Option<String>,
+    /// Whether the application should be activated through D-Bus rather than `exec`
+    pub dbus_activatable: bool,
+}
+
+/// Represents a `[Desktop Action]` group in a desktop entry file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DesktopAction {
+    /// Identifier of the action, as listed in the `Actions` key
+    pub id: String,
+    /// Name of the action
+    pub name: String,
+    /// Command to execute
+    pub exec: String,
 }
 
 /// Modes for running a DesktopFile's `exec` command
@@ -53,80 +100,282 @@ impl DesktopEntry {
         mode: Mode,
         arguments: Vec<String>,
     ) -> Result<()> {
+        // Arguments to `handlr launch` are not necessarily URIs, so they cannot be passed over D-Bus
+        if self.dbus_activatable && (mode == Mode::Open || arguments.is_empty())
+        {
+            match Connection::session()
+                .map_err(Error::from)
+                .and_then(|connection| {
+                    self.dbus_activate(&connection, &arguments)
+                }) {
+                Ok(()) => return Ok(()),
+                Err(e) => debug!(
+                    "Could not activate `{}` through D-Bus, falling back to Exec: {}",
+                    self.file_name.to_string_lossy(),
+                    e
+                ),
+            }
+        }
+
+        for args in self.arg_groups(mode, arguments)? {
+            self.exec_inner(config, args)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the full command line of every process that would be spawned to execute the entry
+    /// Nothing is actually run, and D-Bus activation is not taken into account
+    pub fn cmds(
+        &self,
+        config: &Config,
+        mode: Mode,
+        arguments: Vec<String>,
+    ) -> Result<Vec<Vec<String>>> {
+        self.arg_groups(mode, arguments)?
+            .into_iter()
+            .map(|args| {
+                let (cmd, args) = self.get_cmd(config, args)?;
+                let cmd = self.launch_cmd(config, cmd, args);
+
+                Ok(std::iter::once(cmd.get_program())
+                    .chain(cmd.get_args())
+                    .map(|arg| arg.to_string_lossy().to_string())
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// Split the given arguments into the arguments of each process to spawn
+    /// Entries that only accept a single file or URL are run once for each of them
+    fn arg_groups(
+        &self,
+        mode: Mode,
+        arguments: Vec<String>,
+    ) -> Result<Vec<Vec<String>>> {
         let supports_multiple =
-            self.exec.contains("%F") || self.exec.contains("%U");
-        if arguments.is_empty() {
-            self.exec_inner(config, vec![])?
+            matches!(self.file_field_code(), Some('F' | 'U'));
+        let arguments = match mode {
+            Mode::Open => self.open_args(arguments)?,
+            Mode::Launch => arguments,
+        };
+
+        Ok(if arguments.is_empty() {
+            vec![vec![]]
         } else if supports_multiple || mode == Mode::Launch {
-            self.exec_inner(config, arguments)?;
+            vec![arguments]
         } else {
-            for arg in arguments {
-                self.exec_inner(config, vec![arg])?;
+            arguments.into_iter().map(|arg| vec![arg]).collect()
+        })
+    }
+
+    /// Activate the application through D-Bus, opening the given paths/URLs if any
+    fn dbus_activate(
+        &self,
+        connection: &Connection,
+        args: &[String],
+    ) -> Result<()> {
+        let app_id = self.app_id().ok_or_else(|| {
+            Error::BadPath(self.file_name.to_string_lossy().to_string())
+        })?;
+        let app = DBusApplication::new(connection, &app_id);
+
+        match &self.action {
+            Some(action) => {
+                debug!(
+                    "Activating action `{}` of `{}` through D-Bus",
+                    action, app_id
+                );
+                app.activate_action(action)
             }
-        };
+            None if args.is_empty() => {
+                debug!("Activating `{}` through D-Bus", app_id);
+                app.activate()
+            }
+            None => {
+                let uris = args
+                    .iter()
+                    .map(|arg| to_uri(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                debug!("Opening {:?} with `{}` through D-Bus", uris, app_id);
+                app.open(&uris)
+            }
+        }
+    }
 
-        Ok(())
+    /// Get the application ID, i.e. the desktop file ID without `.desktop`,
+    /// where subdirectories of the `applications` directory are separated by `.`
+    /// e.g. `org.gnome.Foo` for `applications/org/gnome/Foo.desktop`
+    /// Entries outside of an `applications` directory use their file name
+    fn app_id(&self) -> Option<String> {
+        let path = self
+            .location
+            .as_deref()
+            .and_then(|location| {
+                location
+                    .ancestors()
+                    .skip(1)
+                    .find(|dir| {
+                        dir.file_name() == Some(OsStr::new("applications"))
+                    })
+                    .and_then(|dir| location.strip_prefix(dir).ok())
+            })
+            .unwrap_or_else(|| Path::new(&self.file_name));
+
+        path.to_str()?
+            .strip_suffix(".desktop")
+            .filter(|app_id| !app_id.is_empty())
+            .map(|app_id| app_id.replace('/', "."))
     }
 
     /// Internal helper function for `exec`
     #[mutants::skip] // Cannot test directly, runs command
     fn exec_inner(&self, config: &Config, args: Vec<String>) -> Result<()> {
-        let mut cmd = {
-            let (cmd, args) = self.get_cmd(config, args)?;
-            debug!("Executing program \"{}\" with args: {:?}", cmd, args);
-            let mut cmd = Command::new(cmd);
-            cmd.args(args);
-            cmd
-        };
+        let (cmd, args) = self.get_cmd(config, args)?;
+        let mut cmd = self.launch_cmd(config, cmd, args);
+
+        let grace_period =
+            Duration::from_millis(config.config.handler_grace_period);
 
         if self.terminal && config.terminal_output {
-            cmd.spawn()?.wait()?;
+            // Terminal handlers stay attached to handlr's terminal
+            self.spawn(&mut cmd)?.wait()?;
         } else {
-            cmd.stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+            self.spawn_detached(cmd, grace_period)?;
         }
 
         Ok(())
     }
 
+    /// Spawn the given command fully detached and watch it for the given grace period, if any
+    /// If it exits unsuccessfully in that time, an error with its stderr is returned.
+    /// Otherwise, it is left running once handlr exits.
+    #[mutants::skip] // Cannot test directly, depends on timing
+    fn spawn_detached(
+        &self,
+        mut cmd: Command,
+        grace_period: Duration,
+    ) -> Result<()> {
+        detach(&mut cmd, grace_period);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Only waits for the intermediate process,
+        // which exits once the grace period is over or the program exits
+        let output = self.spawn(&mut cmd)?.wait_with_output()?;
+
+        let Ok(status) = <[u8; 4]>::try_from(output.stdout.as_slice()) else {
+            debug!("Handler still running after {:?}", grace_period);
+            return Ok(());
+        };
+
+        let status = ExitStatus::from_raw(i32::from_ne_bytes(status));
+        debug!("Handler exited during grace period with {}", status);
+
+        if status.success() {
+            return Ok(());
+        }
+
+        Err(Error::HandlerFailed(
+            self.display_name(),
+            status,
+            String::from_utf8_lossy(&output.stderr)
+                .trim_end()
+                .to_string(),
+        ))
+    }
+
+    /// Spawn the given command, noting which entry it was for if that fails
+    fn spawn(&self, cmd: &mut Command) -> Result<Child> {
+        cmd.spawn()
+            .map_err(|e| Error::Spawn(self.display_name(), e))
+    }
+
+    /// Whether the entry was made for a regex handler rather than read from a desktop file
+    fn is_regex_handler(&self) -> bool {
+        self.file_name.is_empty()
+    }
+
+    /// Get a name to identify the entry by in messages
+    /// Entries for regex handlers have no file name, so their command is used instead
+    fn display_name(&self) -> String {
+        if self.is_regex_handler() {
+            self.exec.clone()
+        } else {
+            self.file_name.to_string_lossy().to_string()
+        }
+    }
+
+    /// Build the process to spawn for the given command and arguments
+    /// If enabled in the config, it is wrapped in `systemd-run` so it runs in its own transient scope
+    fn launch_cmd(
+        &self,
+        config: &Config,
+        cmd: String,
+        args: Vec<String>,
+    ) -> Command {
+        debug!("Executing program \"{}\" with args: {:?}", cmd, args);
+
+        let mut launch_cmd = if config.config.systemd_scope {
+            let app_id = self.app_id();
+            let unit =
+                scope_unit_name(app_id.as_deref().unwrap_or_else(|| {
+                    Path::new(&cmd)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or(&cmd)
+                }));
+            debug!("Running in transient systemd scope `{}`", unit);
+
+            let mut launch_cmd = Command::new("systemd-run");
+            launch_cmd
+                .args(["--user", "--scope", "--quiet", "--collect", "--unit"])
+                .arg(unit)
+                .arg("--")
+                .arg(cmd)
+                .args(args);
+            launch_cmd
+        } else {
+            let mut launch_cmd = Command::new(cmd);
+            launch_cmd.args(args);
+            launch_cmd
+        };
+
+        launch_cmd.envs(&self.env);
+
+        if let Some(working_dir) = &self.working_dir {
+            debug!("Working directory: {}", working_dir.display());
+            launch_cmd.current_dir(working_dir);
+        }
+
+        launch_cmd
+    }
+
     /// Get the `exec` command, formatted with given arguments
     pub fn get_cmd(
         &self,
         config: &Config,
         args: Vec<String>,
     ) -> Result<(String, Vec<String>)> {
-        let special =
-            AhoCorasick::new_auto_configured(&["%f", "%F", "%u", "%U"]);
-
-        let mut exec = shlex::split(&self.exec).ok_or_else(|| {
+        let exec = split_exec(&self.exec).ok_or_else(|| {
             Error::BadExec(
                 self.exec.clone(),
                 self.file_name.to_string_lossy().to_string(),
             )
         })?;
 
+        let mut has_file_codes = false;
+        let mut exec = exec
+            .iter()
+            .flat_map(|arg| {
+                self.expand_field_codes(arg, &args, &mut has_file_codes)
+            })
+            .collect_vec();
+
         // The desktop entry doesn't contain arguments - we make best effort and append them at
         // the end
-        if special.is_match(&self.exec) {
-            exec = exec
-                .into_iter()
-                .flat_map(|s| match s.as_str() {
-                    "%f" | "%F" | "%u" | "%U" => args.clone(),
-                    s => vec![{
-                        let mut replaced =
-                            String::with_capacity(s.len() + args.len() * 2);
-                        special.replace_all_with(
-                            s,
-                            &mut replaced,
-                            |_, _, dst| {
-                                dst.push_str(args.clone().join(" ").as_str());
-                                false
-                            },
-                        );
-                        replaced
-                    }],
-                })
-                .collect()
-        } else {
+        if !has_file_codes {
             exec.extend_from_slice(&args);
         }
 
@@ -144,6 +393,130 @@ impl DesktopEntry {
         Ok((exec.remove(0), exec))
     }
 
+    /// Get the first of `%f`, `%F`, `%u` or `%U` in the `exec` command, if any
+    pub fn file_field_code(&self) -> Option<char> {
+        split_exec(&self.exec)?
+            .iter()
+            .flat_map(|arg| field_codes(arg))
+            .find(|code| matches!(code, 'f' | 'F' | 'u' | 'U'))
+    }
+
+    /// Convert paths/URLs being opened to what the `exec` command expects
+    /// `%f` and `%F` expect local file paths, so `file://` URLs are converted to paths
+    /// and any other URLs are refused.
+    /// `%u` and `%U` expect URLs, so paths are converted to `file://` URLs.
+    ///
+    /// Entries for regex handlers are left alone, since their commands are written for the paths/URLs their regexes match
+    fn open_args(&self, args: Vec<String>) -> Result<Vec<String>> {
+        if self.is_regex_handler() {
+            return Ok(args);
+        }
+
+        let Some(code) = self.file_field_code() else {
+            return args
+                .into_iter()
+                .map(|arg| match UserPath::from_str(&arg)? {
+                    UserPath::File(path) if path.is_relative() => Ok(self
+                        .resolve_file(path)?
+                        .to_string_lossy()
+                        .into_owned()),
+                    _ => Ok(arg),
+                })
+                .collect();
+        };
+
+        args.into_iter()
+            .map(|arg| match (code, UserPath::from_str(&arg)?) {
+                ('u' | 'U', _) => to_uri(&arg),
+                (_, UserPath::File(path)) => {
+                    Ok(self.resolve_file(path)?.to_string_lossy().into_owned())
+                }
+                (_, UserPath::Url(_)) => {
+                    Err(Error::NonLocalUrl(arg, self.name.clone()))
+                }
+            })
+            .collect()
+    }
+
+    /// Make a file path absolute if the entry sets its own working directory,
+    /// since relative paths would otherwise be resolved against that directory instead
+    fn resolve_file(&self, path: PathBuf) -> Result<PathBuf> {
+        Ok(if self.working_dir.is_some() {
+            std::path::absolute(path)?
+        } else {
+            path
+        })
+    }
+
+    /// Expand the field codes in a single argument of the `exec` command
+    /// See <https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html>
+    ///
+    /// Keeps track of whether any of `%f`, `%F`, `%u` or `%U` were found
+    fn expand_field_codes(
+        &self,
+        arg: &str,
+        args: &[String],
+        has_file_codes: &mut bool,
+    ) -> Vec<String> {
+        // Field codes that take up a whole argument may expand to any number of arguments
+        match arg {
+            "%f" | "%F" | "%u" | "%U" => {
+                *has_file_codes = true;
+                return args.to_vec();
+            }
+            "%i" => {
+                return self
+                    .icon
+                    .iter()
+                    .flat_map(|icon| ["--icon".to_owned(), icon.clone()])
+                    .collect_vec()
+            }
+            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => return Vec::new(),
+            _ => {}
+        }
+
+        // Otherwise, expand field codes inside the argument
+        // Technically not allowed by the spec, but used in the wild (e.g. `sh -c "... %f"`)
+        let mut chars = arg.chars();
+        let mut expanded = String::with_capacity(arg.len());
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('f' | 'F' | 'u' | 'U') => {
+                    *has_file_codes = true;
+                    expanded.push_str(&args.join(" "));
+                }
+                Some('i') => {
+                    expanded.push_str(self.icon.as_deref().unwrap_or_default())
+                }
+                Some('c') => expanded.push_str(&self.name),
+                Some('k') => expanded.push_str(
+                    &self
+                        .location
+                        .as_ref()
+                        .map(|location| location.to_string_lossy())
+                        .unwrap_or_default(),
+                ),
+                Some('%') => expanded.push('%'),
+                // Deprecated field codes
+                Some('d' | 'D' | 'n' | 'N' | 'v' | 'm') => {}
+                // Leave anything unknown alone
+                Some(c) => {
+                    expanded.push('%');
+                    expanded.push(c);
+                }
+                None => expanded.push('%'),
+            }
+        }
+
+        vec![expanded]
+    }
+
     /// Parse a desktop entry file, given a path
     fn parse_file(path: &Path) -> Option<DesktopEntry> {
         // Assume the set locales will not change while handlr is running
@@ -156,7 +529,17 @@ impl DesktopEntry {
             name: fd_entry.name(&LOCALES)?.into_owned(),
             exec: fd_entry.exec()?.to_owned(),
             file_name: path.file_name()?.to_owned(),
+            location: Some(path.to_path_buf()),
+            icon: fd_entry
+                .icon()
+                .filter(|icon| !icon.is_empty())
+                .map(|icon| icon.to_owned()),
             terminal: fd_entry.terminal(),
+            working_dir: fd_entry
+                .desktop_entry("Path")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            env: BTreeMap::new(),
             mime_type: fd_entry
                 .mime_type()
                 .unwrap_or_default()
@@ -169,6 +552,30 @@ impl DesktopEntry {
                 .iter()
                 .map(|&c| c.to_owned())
                 .collect_vec(),
+            hidden: fd_entry.desktop_entry("Hidden") == Some("true"),
+            dbus_activatable: fd_entry.desktop_entry("DBusActivatable")
+                == Some("true"),
+            no_display: fd_entry.no_display(),
+            try_exec: fd_entry
+                .desktop_entry("TryExec")
+                .filter(|try_exec| !try_exec.is_empty())
+                .map(|try_exec| try_exec.to_owned()),
+            only_show_in: split_list(fd_entry.only_show_in()),
+            not_show_in: split_list(fd_entry.not_show_in()),
+            actions: split_list(fd_entry.actions())
+                .into_iter()
+                .filter_map(|id| {
+                    Some(DesktopAction {
+                        name: fd_entry.action_name(&id, &LOCALES)?.into_owned(),
+                        exec: fd_entry
+                            .action_exec(&id)
+                            .filter(|exec| !exec.is_empty())?
+                            .to_owned(),
+                        id,
+                    })
+                })
+                .collect_vec(),
+            action: None,
         };
 
         if !entry.name.is_empty() && !entry.exec.is_empty() {
@@ -192,6 +599,319 @@ impl DesktopEntry {
     pub fn is_terminal_emulator(&self) -> bool {
         self.categories.contains(&"TerminalEmulator".to_string())
     }
+
+    /// Get a desktop entry that runs the given action instead of the main command
+    pub fn action(self, id: &str) -> Result<Self> {
+        let action = self
+            .actions
+            .iter()
+            .find(|action| action.id == id)
+            .cloned()
+            .ok_or_else(|| {
+                Error::UnknownAction(
+                    id.to_owned(),
+                    self.file_name.to_string_lossy().to_string(),
+                )
+            })?;
+
+        Ok(Self {
+            name: format!("{} ({})", self.name, action.name),
+            exec: action.exec,
+            action: Some(action.id),
+            ..self
+        })
+    }
+
+    /// Check if the entry can be used in the current desktop environment
+    /// i.e. it is not hidden, its `TryExec` program is installed,
+    /// and it is not restricted to other desktop environments
+    pub fn is_available(&self) -> bool {
+        let is_installed = self.try_exec.as_deref().is_none_or(is_executable);
+
+        !self.hidden && is_installed && self.is_shown_in(&current_desktops())
+    }
+
+    /// Check if the entry should be shown in any of the given desktop environments
+    /// according to its `OnlyShowIn` and `NotShowIn` keys
+    fn is_shown_in(&self, desktops: &[String]) -> bool {
+        (self.only_show_in.is_empty()
+            || desktops.iter().any(|d| self.only_show_in.contains(d)))
+            && !desktops.iter().any(|d| self.not_show_in.contains(d))
+    }
+}
+
+/// Run the process spawned by the given command in a new session,
+/// so it does not receive SIGHUP when handlr's terminal closes
+#[mutants::skip] // Cannot test directly, affects process spawning
+fn new_session(cmd: &mut Command) {
+    // SAFETY: setsid is async-signal-safe
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Maximum number of bytes of a handler's stderr reported if it fails during the grace period
+/// Kept well below the size of a pipe buffer so the intermediate process never blocks writing it
+const MAX_REPORTED_STDERR: usize = 16 * 1024;
+
+/// Fully daemonize the process spawned by the given command
+/// It is run in a new session, and it is forked again so it is reparented to init
+/// (or the nearest subreaper) rather than becoming a zombie if handlr is embedded in a long-running process
+///
+/// With a non-zero grace period, the intermediate process watches the program until it is over.
+/// The program's stderr is forwarded to the intermediate process' stderr in that time,
+/// and its raw wait status is written to the intermediate process' stdout if it exits.
+#[mutants::skip] // Cannot test directly, affects process spawning
+fn detach(cmd: &mut Command, grace_period: Duration) {
+    new_session(cmd);
+    let grace_period =
+        i64::try_from(grace_period.as_millis()).unwrap_or(i64::MAX);
+    // SAFETY: only async-signal-safe functions are called between fork and exec
+    unsafe {
+        cmd.pre_exec(move || fork_detached(grace_period));
+    }
+}
+
+/// Fork the program to exec from the intermediate process spawned by `detach`
+///
+/// # Safety
+/// Must only be called between fork and exec, and only calls async-signal-safe functions
+#[mutants::skip] // Cannot test directly, affects process spawning
+unsafe fn fork_detached(grace_period: i64) -> std::io::Result<()> {
+    let null =
+        libc::open(c"/dev/null".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+    if null == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let watch = grace_period > 0;
+    // Read and write ends of the program's stderr
+    let mut stderr = [null, null];
+    if watch && libc::pipe2(stderr.as_mut_ptr(), libc::O_CLOEXEC) == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    match libc::fork() {
+        -1 => Err(std::io::Error::last_os_error()),
+        // The grandchild continues on to exec the program
+        0 => {
+            if libc::dup2(null, libc::STDOUT_FILENO) == -1
+                || libc::dup2(stderr[1], libc::STDERR_FILENO) == -1
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }
+        // The intermediate child exits so it can be reaped immediately,
+        // after watching the program if there is a grace period
+        pid => {
+            if watch {
+                libc::close(stderr[1]);
+                watch_detached(pid, stderr[0], grace_period);
+            }
+            libc::_exit(0)
+        }
+    }
+}
+
+/// Watch the program forked by `fork_detached` for the given grace period
+/// Its stderr is only read during the grace period and then handed to a process that discards it,
+/// so the program does not get SIGPIPE or block writing to it later on
+///
+/// # Safety
+/// Must only be called between fork and exec, and only calls async-signal-safe functions
+#[mutants::skip] // Cannot test directly, affects process spawning
+unsafe fn watch_detached(
+    pid: libc::pid_t,
+    stderr: libc::c_int,
+    grace_period: i64,
+) {
+    let now = || {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time);
+        time.tv_sec * 1000 + time.tv_nsec / 1_000_000
+    };
+    let deadline = now().saturating_add(grace_period);
+
+    let mut buf = [0u8; 4096];
+    let mut reported = 0;
+    let mut stderr_open = true;
+    let mut status = 0;
+
+    // Forward whatever is available on the program's stderr, waiting at most the given time
+    // Returns whether anything was forwarded, or `None` once it is closed
+    let mut forward = |timeout: libc::c_int| {
+        let mut fd = libc::pollfd {
+            fd: stderr,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if libc::poll(&mut fd, 1, timeout) <= 0 {
+            return Some(false);
+        }
+        let read = libc::read(stderr, buf.as_mut_ptr().cast(), buf.len());
+        if read <= 0 {
+            return None;
+        }
+        let len = (read as usize).min(MAX_REPORTED_STDERR - reported);
+        libc::write(libc::STDERR_FILENO, buf.as_ptr().cast(), len);
+        reported += len;
+        Some(true)
+    };
+
+    let exited = loop {
+        if libc::waitpid(pid, &mut status, libc::WNOHANG) == pid {
+            break true;
+        }
+
+        let remaining = deadline - now();
+        if remaining <= 0 {
+            break false;
+        }
+
+        if stderr_open {
+            stderr_open = forward(remaining.min(10) as libc::c_int).is_some();
+        } else {
+            libc::poll(
+                std::ptr::null_mut(),
+                0,
+                remaining.min(10) as libc::c_int,
+            );
+        }
+    };
+
+    if exited {
+        // Pick up anything written right before the program exited
+        while stderr_open {
+            match forward(0) {
+                Some(true) => {}
+                Some(false) => break,
+                None => stderr_open = false,
+            }
+        }
+        libc::write(
+            libc::STDOUT_FILENO,
+            (&raw const status).cast(),
+            std::mem::size_of_val(&status),
+        );
+    }
+
+    // Let handlr know the grace period is over
+    libc::close(libc::STDOUT_FILENO);
+    libc::close(libc::STDERR_FILENO);
+
+    // The program or anything it spawned may still write to stderr
+    if stderr_open && libc::fork() == 0 {
+        // Do not keep any of handlr's pipes open, including the one std uses
+        // to report exec errors, whose descriptor is not known here
+        libc::dup2(stderr, libc::STDIN_FILENO);
+        let max_fd = match libc::sysconf(libc::_SC_OPEN_MAX) {
+            -1 => 1024,
+            max => max.min(libc::c_int::MAX.into()) as libc::c_int,
+        };
+        for fd in 1..max_fd {
+            libc::close(fd);
+        }
+        while libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len())
+            > 0
+        {}
+        libc::_exit(0);
+    }
+}
+
+/// Get a unique name for a transient systemd scope to run an application in
+/// See <https://systemd.io/DESKTOP_ENVIRONMENTS/#xdg-standardization-for-applications>
+fn scope_unit_name(app_id: &str) -> String {
+    // Does not need to be random, just unique
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default()
+        ^ u128::from(std::process::id());
+
+    format!(
+        "app-handlr-{}-{:x}.scope",
+        systemd_escape(app_id),
+        unique as u64
+    )
+}
+
+/// Escape a string for use in a systemd unit name, like `systemd-escape`
+/// `-` is escaped too, since it separates the components of the unit name
+fn systemd_escape(s: &str) -> String {
+    s.bytes()
+        .enumerate()
+        .map(|(index, byte)| match byte {
+            b'.' if index == 0 => format!("\\x{:02x}", byte),
+            b'/' => "-".to_owned(),
+            b if b.is_ascii_alphanumeric()
+                || matches!(b, b':' | b'_' | b'.') =>
+            {
+                char::from(b).to_string()
+            }
+            b => format!("\\x{:02x}", b),
+        })
+        .collect()
+}
+
+/// Convert a path/URL to a URL, so local paths become `file://` URLs
+fn to_uri(arg: &str) -> Result<String> {
+    match UserPath::from_str(arg)? {
+        UserPath::File(path) => {
+            Url::from_file_path(std::path::absolute(&path)?)
+                .map(String::from)
+                .map_err(|_| Error::BadPath(arg.to_owned()))
+        }
+        UserPath::Url(_) => Ok(arg.to_owned()),
+    }
+}
+
+/// Split a list of strings from a desktop entry, ignoring empty items
+fn split_list(list: Option<Vec<&str>>) -> Vec<String> {
+    list.unwrap_or_default()
+        .into_iter()
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_owned())
+        .collect_vec()
+}
+
+/// Check if the given program is an executable file,
+/// searching `$PATH` if it is not an absolute path
+fn is_executable(program: &str) -> bool {
+    let is_executable_file = |path: &Path| {
+        path.metadata().is_ok_and(|metadata| {
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        })
+    };
+
+    let path = Path::new(program);
+
+    if path.is_absolute() {
+        is_executable_file(path)
+    } else {
+        std::env::var_os("PATH").is_some_and(|paths| {
+            std::env::split_paths(&paths)
+                .any(|dir| is_executable_file(&dir.join(program)))
+        })
+    }
+}
+
+/// Get the names of the current desktop environment(s) from `$XDG_CURRENT_DESKTOP`
+pub fn current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(|desktop| desktop.to_owned())
+        .collect_vec()
 }
 
 impl TryFrom<PathBuf> for DesktopEntry {
@@ -204,8 +924,13 @@ impl TryFrom<PathBuf> for DesktopEntry {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::DesktopHandler;
+    use crate::common::{DesktopHandler, Handleable};
     use similar_asserts::assert_eq;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+    use zbus::zvariant::OwnedValue;
 
     // Helper function to test getting the command from the Exec field
     fn test_get_cmd(
@@ -299,4 +1024,381 @@ mod tests {
             vec!["start", "--cwd", ".", "-e", "hx", "test"],
         )
     }
+
+    #[test]
+    fn availability() -> Result<()> {
+        let hidden = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/hidden.desktop",
+        ))?;
+        assert!(hidden.hidden);
+        assert!(!hidden.is_available());
+
+        let missing_try_exec = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/missing_try_exec.desktop",
+        ))?;
+        assert_eq!(
+            missing_try_exec.try_exec.as_deref(),
+            Some("handlr-missing-program")
+        );
+        assert!(!is_executable("handlr-missing-program"));
+        assert!(is_executable("sh"));
+        assert!(!missing_try_exec.is_available());
+
+        let only_show_in = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/only_show_in.desktop",
+        ))?;
+        assert!(only_show_in.no_display);
+        assert!(only_show_in.is_shown_in(&["KDE".to_string()]));
+        assert!(only_show_in.is_shown_in(&["sway".into(), "LXQt".into()]));
+        assert!(!only_show_in.is_shown_in(&["KDE".into(), "GNOME".into()]));
+        assert!(!only_show_in.is_shown_in(&[]));
+
+        let helix = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/Helix.desktop",
+        ))?;
+        assert!(helix.is_shown_in(&[]));
+        assert!(!helix.no_display);
+        assert_eq!(helix.try_exec.as_deref(), Some("sh"));
+        assert!(helix.is_available());
+
+        Ok(())
+    }
+
+    #[test]
+    fn actions() -> Result<()> {
+        let entry = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/actions.desktop",
+        ))?;
+
+        // Actions without an `Exec` key are ignored
+        assert_eq!(
+            entry.actions.iter().map(|a| a.id.as_str()).collect_vec(),
+            vec!["new-window", "new-private-window"]
+        );
+
+        let handler = DesktopHandler::assume_valid(
+            "tests/assets/actions.desktop:new-private-window".into(),
+        );
+        let private_window = handler.get_entry()?;
+
+        assert_eq!(private_window.name, "Firefox (New Private Window)");
+        test_get_cmd(
+            &private_window,
+            &Config::default(),
+            "firefox",
+            vec!["--private-window", "test"],
+        )?;
+
+        assert!(matches!(
+            entry.action("profile-manager-window"),
+            Err(Error::UnknownAction(..))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn field_codes() -> Result<()> {
+        let mut config = Config::default();
+        config.terminal_output = true;
+
+        // Get the command of every desktop entry in the test assets
+        let cmds = std::fs::read_dir("tests/assets")?
+            .map(|file| file.map(|file| file.path()))
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| {
+                path.extension().and_then(|x| x.to_str()) == Some("desktop")
+            })
+            .filter_map(|path| DesktopEntry::try_from(path).ok())
+            .map(|entry| {
+                let cmd = entry
+                    .get_cmd(&config, vec!["a".into(), "b c".into()])
+                    .map_err(|e| e.to_string());
+                (entry.file_name.to_string_lossy().to_string(), cmd)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        insta::assert_debug_snapshot!(cmds);
+
+        let field_codes = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/field_codes.desktop",
+        ))?;
+        assert_eq!(field_codes.working_dir, Some(PathBuf::from("/tmp")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_paths_with_working_dir() -> Result<()> {
+        let mut config = Config::default();
+        config.terminal_output = true;
+        let readme = std::env::current_dir()?.join("README.md");
+
+        // Path=/tmp, so README.md would otherwise be looked for in /tmp
+        let field_codes = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/field_codes.desktop",
+        ))?;
+        let cmd = &field_codes.cmds(
+            &config,
+            Mode::Open,
+            vec!["README.md".into(), "/etc/hosts".into()],
+        )?[0];
+        assert_eq!(
+            cmd[cmd.len() - 2..],
+            [readme.to_string_lossy().to_string(), "/etc/hosts".into()]
+        );
+
+        // Entries without a working directory are given paths as-is
+        let helix = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/Helix.desktop",
+        ))?;
+        assert_eq!(
+            helix.cmds(&config, Mode::Open, vec!["README.md".into()])?[0]
+                .last()
+                .map(String::as_str),
+            Some("README.md")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_args() -> Result<()> {
+        let args = |args: &[&str]| {
+            args.iter().map(|arg| arg.to_string()).collect_vec()
+        };
+
+        // %F
+        let helix = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/Helix.desktop",
+        ))?;
+        assert_eq!(
+            helix.open_args(args(&["file:///tmp/a%20b.txt", "README.md"]))?,
+            args(&["/tmp/a b.txt", "README.md"])
+        );
+        assert!(matches!(
+            helix.open_args(args(&["README.md", "sftp://example.com/file"])),
+            Err(Error::NonLocalUrl(..))
+        ));
+
+        // %u
+        let firefox = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/actions.desktop",
+        ))?;
+        assert_eq!(
+            firefox.open_args(args(&[
+                "/tmp/a b.txt",
+                "README.md",
+                "https://example.com"
+            ]))?,
+            vec![
+                "file:///tmp/a%20b.txt".to_string(),
+                Url::from_file_path(std::env::current_dir()?.join("README.md"))
+                    .expect("Could not convert path to URL")
+                    .to_string(),
+                "https://example.com".to_string(),
+            ]
+        );
+
+        // No field codes
+        let wezterm = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/org.wezfurlong.wezterm.desktop",
+        ))?;
+        assert_eq!(
+            wezterm.open_args(args(&["sftp://example.com/file"]))?,
+            args(&["sftp://example.com/file"])
+        );
+
+        // Regex handlers are run as written
+        let regex_handler = DesktopEntry::fake_entry("mpv %f", false);
+        assert_eq!(
+            regex_handler.open_args(args(&["https://example.com/video"]))?,
+            args(&["https://example.com/video"])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn grace_period() -> Result<()> {
+        let mut config = Config::default();
+        config.config.handler_grace_period = 5000;
+
+        DesktopEntry::fake_entry("true", false).exec_inner(&config, vec![])?;
+
+        let failing = DesktopEntry::fake_entry(
+            r#"sh -c "echo 'something went wrong' >&2; exit 3""#,
+            false,
+        );
+
+        match failing.exec_inner(&config, vec![]) {
+            Err(Error::HandlerFailed(name, status, stderr)) => {
+                assert_eq!(name, failing.exec);
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "something went wrong");
+            }
+            result => panic!("Expected handler to fail, got {:?}", result),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn systemd_scope() -> Result<()> {
+        let mut config = Config::default();
+        config.config.systemd_scope = true;
+
+        let entry = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/field_codes.desktop",
+        ))?;
+        let cmd = entry.launch_cmd(
+            &config,
+            "field-codes".into(),
+            vec!["a".into(), "b c".into()],
+        );
+        let args = cmd
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect_vec();
+
+        assert_eq!(cmd.get_program(), "systemd-run");
+        assert_eq!(
+            args[..5],
+            ["--user", "--scope", "--quiet", "--collect", "--unit"]
+        );
+        assert!(regex::Regex::new(
+            r"^app-handlr-field_codes-[0-9a-f]+\.scope$"
+        )?
+        .is_match(&args[5]));
+        assert_eq!(args[6..], ["--", "field-codes", "a", "b c"]);
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/tmp")));
+
+        assert_eq!(systemd_escape("org.gnome.Nautilus"), "org.gnome.Nautilus");
+        assert_eq!(systemd_escape("kde-foo"), r"kde\x2dfoo");
+        assert_eq!(systemd_escape(".hidden app"), r"\x2ehidden\x20app");
+
+        Ok(())
+    }
+
+    /// Stand-in for a D-Bus activatable application, keeping track of the methods called on it
+    struct FakeApplication(Arc<Mutex<Vec<String>>>);
+
+    #[zbus::interface(name = "org.freedesktop.Application")]
+    impl FakeApplication {
+        fn activate(&self, _platform_data: HashMap<String, OwnedValue>) {
+            self.0.lock().unwrap().push("Activate".into());
+        }
+
+        fn open(
+            &self,
+            uris: Vec<String>,
+            _platform_data: HashMap<String, OwnedValue>,
+        ) {
+            self.0.lock().unwrap().push(format!("Open {:?}", uris));
+        }
+
+        fn activate_action(
+            &self,
+            action_name: String,
+            _parameter: Vec<OwnedValue>,
+            _platform_data: HashMap<String, OwnedValue>,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("ActivateAction {}", action_name));
+        }
+    }
+
+    /// Private session bus that is shut down when dropped
+    struct SessionBus(std::process::Child);
+
+    impl Drop for SessionBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn app_ids() -> Result<()> {
+        let app_id = |path: &str| -> Result<Option<String>> {
+            Ok(DesktopEntry::try_from(PathBuf::from(path))?.app_id())
+        };
+
+        assert_eq!(
+            app_id("tests/assets/org.example.Test.desktop")?,
+            Some("org.example.Test".into())
+        );
+        // Subdirectories are part of the ID
+        assert_eq!(
+            app_id("tests/assets/data_home/applications/kde/foo.desktop")?,
+            Some("kde.foo".into())
+        );
+        assert_eq!(
+            app_id("tests/assets/data_home/applications/shadowed.desktop")?,
+            Some("shadowed".into())
+        );
+        assert_eq!(DesktopEntry::fake_entry("mpv %u", false).app_id(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon, run with `cargo test -- --ignored`"]
+    fn dbus_activation() -> Result<()> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let mut address = String::new();
+        std::io::BufRead::read_line(
+            &mut std::io::BufReader::new(
+                daemon.stdout.take().expect("Could not get stdout"),
+            ),
+            &mut address,
+        )?;
+        let _bus = SessionBus(daemon);
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let _service =
+            zbus::blocking::connection::Builder::address(address.trim())?
+                .name("org.example.Test")?
+                .serve_at("/org/example/Test", FakeApplication(calls.clone()))?
+                .build()?;
+
+        let connection =
+            zbus::blocking::connection::Builder::address(address.trim())?
+                .build()?;
+
+        let entry = DesktopEntry::try_from(PathBuf::from(
+            "tests/assets/org.example.Test.desktop",
+        ))?;
+        assert!(entry.dbus_activatable);
+
+        entry.dbus_activate(&connection, &[])?;
+        entry.dbus_activate(
+            &connection,
+            &["/tmp/a b.txt".into(), "https://example.com".into()],
+        )?;
+        entry
+            .clone()
+            .action("new-window")?
+            .dbus_activate(&connection, &[])?;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "Activate".to_string(),
+                r#"Open ["file:///tmp/a%20b.txt", "https://example.com"]"#
+                    .to_string(),
+                "ActivateAction new-window".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
 }
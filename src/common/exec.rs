@@ -0,0 +1,203 @@
+/// Split the value of a desktop entry's `Exec` key into arguments
+/// See <https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html>
+///
+/// The value is first unescaped as a string, then split on unquoted whitespace.
+/// Inside double quotes, `"`, `` ` ``, `$` and `\` can be escaped with a backslash.
+/// Single quotes are not part of the spec, but are commonly used, so they are treated like in a shell.
+///
+/// Returns `None` if a quote is left unterminated.
+pub fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let exec = unescape(exec);
+    let mut chars = exec.chars();
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Needed to keep empty quoted arguments
+    let mut in_arg = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '`' | '$' | '\\') => arg.push(c),
+                            c => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            c => {
+                in_arg = true;
+                arg.push(c);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(arg);
+    }
+
+    Some(args)
+}
+
+/// Get the field codes used in a single argument of an `Exec` key, in order
+/// e.g. `--name=%c` yields `c`, but `100%%` yields nothing
+pub fn field_codes(arg: &str) -> Vec<char> {
+    let mut chars = arg.chars();
+    let mut codes = Vec::new();
+
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('%') | None => {}
+                Some(code) => codes.push(code),
+            }
+        }
+    }
+
+    codes
+}
+
+/// Quote a single argument so `split_exec` turns it back into exactly the same argument
+/// Field codes are not escaped, so `%` must already be escaped as `%%` where needed
+pub fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+
+    for c in arg.chars() {
+        match c {
+            // Backslashes are unescaped once as a string, then once more inside quotes
+            '\\' => quoted.push_str(r"\\\\"),
+            '"' | '`' | '$' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Unescape a desktop entry string value
+/// Unknown escape sequences are kept as-is so they can be handled when splitting `Exec`
+fn unescape(value: &str) -> String {
+    let mut chars = value.chars();
+    let mut unescaped = String::with_capacity(value.len());
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn split_exec_quoting() {
+        let split = split_exec;
+
+        assert_eq!(
+            split(r#"program  "a b" "" 'c d' e\ f"#),
+            Some(vec![
+                "program".into(),
+                "a b".into(),
+                "".into(),
+                "c d".into(),
+                "e f".into()
+            ])
+        );
+        // `\\` is unescaped as a string first, so it becomes a single backslash before quoting rules apply
+        assert_eq!(
+            split(r#"sh -c "echo \\"\\$HOME\\" \\\\ \\`x\\`""#),
+            Some(vec![
+                "sh".into(),
+                "-c".into(),
+                r#"echo "$HOME" \ `x`"#.into()
+            ])
+        );
+        // Common but technically incorrect escaping is also accepted
+        assert_eq!(
+            split(r#"sh -c "echo \"hi\"""#),
+            Some(vec!["sh".into(), "-c".into(), r#"echo "hi""#.into()])
+        );
+        // `\s` and `\t` are unescaped before splitting, so they only keep arguments together when quoted
+        assert_eq!(
+            split(r#""a\sb"\tc\sd"#),
+            Some(vec!["a b".into(), "c".into(), "d".into()])
+        );
+        assert_eq!(split(r#"program "unterminated"#), None);
+        assert_eq!(split("program 'unterminated"), None);
+    }
+
+    #[test]
+    fn quote_args() {
+        for arg in [
+            "plain",
+            "with spaces",
+            "",
+            r#"quotes " ' and \ \s \\ $HOME `x`"#,
+        ] {
+            assert_eq!(
+                split_exec(&quote_exec_arg(arg)),
+                Some(vec![arg.to_owned()])
+            );
+        }
+    }
+
+    #[test]
+    fn find_field_codes() {
+        assert_eq!(field_codes("%U"), vec!['U']);
+        assert_eq!(field_codes("--name=%c:%k"), vec!['c', 'k']);
+        assert_eq!(field_codes("100%%f"), Vec::<char>::new());
+        assert_eq!(field_codes("trailing%"), Vec::<char>::new());
+    }
+}
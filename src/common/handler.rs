@@ -1,6 +1,10 @@
 use crate::{
-    common::{DesktopEntry, ExecMode, UserPath},
-    config::Config,
+    apps::SystemApps,
+    common::{
+        exec::{quote_exec_arg, split_exec},
+        DesktopEntry, ExecMode, PathKind, UserPath,
+    },
+    config::{Config, RegexTrace, Trace},
     error::{Error, Result},
 };
 use derive_more::{Deref, Display};
@@ -8,14 +12,17 @@ use enum_dispatch::enum_dispatch;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
-    ffi::OsString,
+    ffi::{OsStr, OsString},
     fmt::Display,
     hash::{Hash, Hasher},
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
     str::FromStr,
 };
+use tabled::Tabled;
 use tracing::{debug, info, warn};
+use wildmatch::WildMatch;
 
 /// Represents a program or command that is used to open a file
 #[enum_dispatch(Handleable)]
@@ -43,6 +50,14 @@ pub trait Handleable {
     fn open(&self, config: &Config, args: Vec<String>) -> Result<()> {
         self.get_entry()?.exec(config, ExecMode::Open, args)
     }
+    /// Get the command lines that would be run to open the given paths with the handler
+    fn open_cmds(
+        &self,
+        config: &Config,
+        args: Vec<String>,
+    ) -> Result<Vec<Vec<String>>> {
+        self.get_entry()?.cmds(config, ExecMode::Open, args)
+    }
 }
 
 /// Represents a handler defined in a desktop file
@@ -66,7 +81,20 @@ impl FromStr for DesktopHandler {
 
 impl Handleable for DesktopHandler {
     fn get_entry(&self) -> Result<DesktopEntry> {
-        DesktopEntry::try_from(Self::get_path(&self.0)?)
+        let (name, action) = self.split_action();
+        let entry = DesktopEntry::try_from(Self::get_path(name)?)?;
+
+        match action {
+            Some(action) => entry.action(action),
+            None => Ok(entry),
+        }
+    }
+
+    /// Open the given paths with the handler, using any environment variables set for it in the config
+    #[mutants::skip] // Cannot test directly, runs commands
+    fn open(&self, config: &Config, args: Vec<String>) -> Result<()> {
+        self.get_entry_with_env(config)?
+            .exec(config, ExecMode::Open, args)
     }
 }
 
@@ -76,18 +104,34 @@ impl DesktopHandler {
         Self(name)
     }
 
+    /// Get the handler's desktop entry, including any environment variables set for it in the config
+    fn get_entry_with_env(&self, config: &Config) -> Result<DesktopEntry> {
+        let mut entry = self.get_entry()?;
+        entry.env.extend(config.config.get_handler_env(self));
+        Ok(entry)
+    }
+
+    /// Split the handler into the desktop file ID and the action to run, if any
+    /// e.g. `firefox.desktop:new-private-window`
+    pub fn split_action(&self) -> (&OsStr, Option<&str>) {
+        self.0
+            .to_str()
+            .and_then(|handler| handler.rsplit_once(':'))
+            .filter(|(name, action)| {
+                name.ends_with(".desktop") && !action.is_empty()
+            })
+            .map_or((self.0.as_os_str(), None), |(name, action)| {
+                (OsStr::new(name), Some(action))
+            })
+    }
+
     /// Get the path of a given desktop entry file
-    pub fn get_path(name: &std::ffi::OsStr) -> Result<PathBuf> {
+    pub fn get_path(name: &OsStr) -> Result<PathBuf> {
         if cfg!(test) {
             Ok(PathBuf::from(name))
         } else {
-            let mut path = PathBuf::from("applications");
-            path.push(name);
-            Ok(xdg::BaseDirectories::new()?
-                .find_data_file(path)
-                .ok_or_else(|| {
-                    Error::NotFound(name.to_string_lossy().into())
-                })?)
+            SystemApps::find_desktop_file(name)?
+                .ok_or_else(|| Error::NotFound(name.to_string_lossy().into()))
         }
     }
 
@@ -95,7 +139,17 @@ impl DesktopHandler {
     #[mutants::skip] // Cannot test directly, runs command
     pub fn launch(&self, config: &Config, args: Vec<String>) -> Result<()> {
         info!("Launching `{}` with args: {:?}", self, args);
-        self.get_entry()?.exec(config, ExecMode::Launch, args)
+        self.get_entry_with_env(config)?
+            .exec(config, ExecMode::Launch, args)
+    }
+
+    /// Get the command lines that would be run to launch the DesktopHandler's desktop entry
+    pub fn launch_cmds(
+        &self,
+        config: &Config,
+        args: Vec<String>,
+    ) -> Result<Vec<Vec<String>>> {
+        self.get_entry()?.cmds(config, ExecMode::Launch, args)
     }
 
     /// Issue a warning if the given handler is invalid
@@ -107,58 +161,347 @@ impl DesktopHandler {
 }
 
 /// Represents a regex handler from the config
-#[derive(Display, Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(
+    Display, Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+// Deserialization is wrapped to check that the handler can match anything
+#[serde(remote = "Self")]
 #[display(fmt = "\"{}\" (Regex Handler)", exec)]
 pub struct RegexHandler {
     exec: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     terminal: bool,
+    #[serde(default, skip_serializing_if = "regex::RegexSet::is_empty")]
     regexes: RegexSet,
+    /// Glob patterns matching local files by their absolute path, as an alternative to regexes
+    #[serde(default, skip_serializing_if = "GlobSet::is_empty")]
+    globs: GlobSet,
+    /// Extra environment variables to run the program with
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    /// Mimes the handler is limited to, which may contain wildcards (e.g. `text/*`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mimes: Vec<String>,
+    /// URL schemes the handler is limited to, where local files have the `file` scheme
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    schemes: Vec<String>,
+    /// Kind of path the handler is limited to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<PathKind>,
+    /// Handlers with a higher priority are tried first
+    /// Handlers with the same priority are tried in the order they are defined
+    #[serde(default, skip_serializing_if = "is_zero")]
+    priority: i64,
+}
+
+impl Serialize for RegexHandler {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Self::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexHandler {
+    /// Deserialize a handler, rejecting ones without any regexes or globs
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let handler = Self::deserialize(deserializer)?;
+
+        if handler.regexes.is_empty() && handler.globs.is_empty() {
+            return Err(serde::de::Error::custom(Error::NoPatterns(
+                handler.exec,
+            )));
+        }
+
+        Ok(handler)
+    }
 }
 
 impl RegexHandler {
-    /// Test if a given path matches the handler's regex
-    fn is_match(&self, path: &str) -> bool {
-        let is_match = self.regexes.is_match(path);
-
-        if is_match {
-            let matches = self
-                .regexes
-                .matches(path)
-                .into_iter()
-                .map(|index| &self.regexes.patterns()[index])
-                .collect_vec();
-            debug!(
-                "Regex matches found in `{}` for `{}`: {:?}",
-                self, path, matches
-            );
+    /// Test if a given path matches the handler's regexes or globs and passes its filters
+    fn test(&self, path: &UserPath) -> RegexTrace {
+        let matches = self
+            .matching_patterns(path)
+            .into_iter()
+            .map(str::to_owned)
+            .collect_vec();
+
+        let filtered = if matches.is_empty() {
+            debug!("No matches found in `{}` for `{}`", self, path);
+            None
         } else {
-            debug!("No regex matches found in `{}` for `{}`", self, path);
+            debug!("Matches found in `{}` for `{}`: {:?}", self, path, matches);
+            self.filter_mismatch(path).inspect(|reason| {
+                debug!("`{}` does not apply to `{}`: {}", self, path, reason)
+            })
+        };
+
+        RegexTrace {
+            handler: self.to_string(),
+            priority: self.priority,
+            matches,
+            filtered,
+        }
+    }
+
+    /// Check a given path against the handler's `mimes`, `schemes` and `kind` filters
+    /// Filters that are not set allow anything
+    ///
+    /// Returns why the path does not pass, if it does not
+    pub fn filter_mismatch(&self, path: &UserPath) -> Option<String> {
+        if let Some(kind) = self.kind.filter(|kind| *kind != path.kind()) {
+            return Some(format!("path is a {}, not a {}", path.kind(), kind));
+        }
+
+        let scheme = path.scheme();
+        if !self.schemes.is_empty()
+            && !self.schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+        {
+            return Some(format!("scheme `{}` is not allowed", scheme));
+        }
+
+        if !self.mimes.is_empty() {
+            // Only detect the mime when needed, since it may mean reading the file
+            let mime = match path.get_mime() {
+                Ok(mime) => mime,
+                Err(e) => return Some(format!("could not get mime: {}", e)),
+            };
+            if !self
+                .mimes
+                .iter()
+                .any(|m| WildMatch::new(m).matches(mime.essence_str()))
+            {
+                return Some(format!("mime `{}` is not allowed", mime));
+            }
         }
 
-        is_match
+        None
+    }
+
+    /// Get the handler's regexes and globs that match a given path
+    fn matching_patterns(&self, path: &UserPath) -> Vec<&str> {
+        self.regexes
+            .matches(&path.to_string())
+            .into_iter()
+            .map(|index| self.regexes.patterns()[index].as_str())
+            .chain(self.globs.matching_patterns(path))
+            .collect_vec()
+    }
+
+    /// Get the desktop entries to run to open the given paths, along with the paths each one opens
+    /// Paths whose capture groups are used in `exec` get an entry of their own,
+    /// while consecutive paths that do not are opened together
+    fn open_entries(
+        &self,
+        args: Vec<String>,
+    ) -> Result<Vec<(DesktopEntry, Vec<String>)>> {
+        let mut entries: Vec<(DesktopEntry, Vec<String>)> = Vec::new();
+        let mut last_expanded = true;
+
+        for arg in args {
+            match self.expand_captures(&arg)? {
+                Some(exec) => {
+                    let mut entry = self.get_entry()?;
+                    entry.exec = exec;
+                    // Only pass the path on if the command asks for it
+                    let args = if entry.file_field_code().is_some() {
+                        vec![arg]
+                    } else {
+                        vec![]
+                    };
+                    entries.push((entry, args));
+                    last_expanded = true;
+                }
+                None if !last_expanded => {
+                    if let Some((_, args)) = entries.last_mut() {
+                        args.push(arg);
+                    }
+                }
+                None => {
+                    entries.push((self.get_entry()?, vec![arg]));
+                    last_expanded = false;
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            entries.push((self.get_entry()?, vec![]));
+        }
+
+        Ok(entries)
+    }
+
+    /// Substitute capture groups from the first regex matching a given path into `exec`
+    /// `{name}` is replaced with a named group and `{1}` with a numbered one, while `{0}` is the whole match.
+    /// Placeholders that do not name a group in the regex are left as-is.
+    ///
+    /// Returns `None` if no capture groups are used
+    fn expand_captures(&self, path: &str) -> Result<Option<String>> {
+        let Some(regex) = self.regexes.first_match(path) else {
+            return Ok(None);
+        };
+        let Some(captures) = regex.captures(path) else {
+            return Ok(None);
+        };
+
+        let mut expanded = false;
+        let exec = split_exec(&self.exec)
+            .ok_or_else(|| Error::BadExec(self.exec.clone(), self.to_string()))?
+            .iter()
+            .map(|arg| {
+                quote_exec_arg(&substitute_captures(
+                    arg,
+                    regex,
+                    &captures,
+                    &mut expanded,
+                ))
+            })
+            .join(" ");
+
+        if expanded {
+            debug!("Expanded capture groups in `{}`: {}", self, exec);
+        }
+
+        Ok(expanded.then_some(exec))
     }
 }
 
 impl Handleable for RegexHandler {
     fn get_entry(&self) -> Result<DesktopEntry> {
-        Ok(DesktopEntry::fake_entry(&self.exec, self.terminal))
+        let mut entry = DesktopEntry::fake_entry(&self.exec, self.terminal);
+        entry.env = self.env.clone();
+        Ok(entry)
+    }
+
+    /// Open the given paths with the handler, substituting capture groups into its command
+    #[mutants::skip] // Cannot test directly, runs commands
+    fn open(&self, config: &Config, args: Vec<String>) -> Result<()> {
+        for (entry, args) in self.open_entries(args)? {
+            entry.exec(config, ExecMode::Open, args)?;
+        }
+
+        Ok(())
+    }
+
+    fn open_cmds(
+        &self,
+        config: &Config,
+        args: Vec<String>,
+    ) -> Result<Vec<Vec<String>>> {
+        Ok(self
+            .open_entries(args)?
+            .into_iter()
+            .map(|(entry, args)| entry.cmds(config, ExecMode::Open, args))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
     }
 }
 
+/// Helper function to substitute capture group placeholders in a single argument of a regex handler's command
+/// Keeps track of whether anything was substituted
+fn substitute_captures(
+    arg: &str,
+    regex: &regex::Regex,
+    captures: &regex::Captures,
+    expanded: &mut bool,
+) -> String {
+    let mut substituted = String::with_capacity(arg.len());
+    let mut rest = arg;
+
+    while let Some(start) = rest.find('{') {
+        substituted.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let group = rest.find('}').map(|end| &rest[..end]).filter(|name| {
+            match name.parse::<usize>() {
+                Ok(index) => index < regex.captures_len(),
+                Err(_) => regex.capture_names().flatten().any(|n| n == *name),
+            }
+        });
+
+        match group {
+            Some(name) => {
+                let value = match name.parse::<usize>() {
+                    Ok(index) => captures.get(index),
+                    Err(_) => captures.name(name),
+                }
+                .map_or("", |value| value.as_str());
+
+                // Captured text must not be mistaken for field codes
+                substituted.push_str(&value.replace('%', "%%"));
+                rest = &rest[name.len() + 1..];
+                *expanded = true;
+            }
+            None => substituted.push('{'),
+        }
+    }
+
+    substituted.push_str(rest);
+    substituted
+}
+
+/// Helper function to skip serializing a regex handler's priority if it is the default
+fn is_zero(priority: &i64) -> bool {
+    *priority == 0
+}
+
 /// Helper struct needed because regex::RegexSet does not implement Hash
-#[derive(Deref, Debug, Clone, Deserialize)]
-struct RegexSet(#[serde(with = "serde_regex")] regex::RegexSet);
+/// Also keeps each regex compiled on its own, since a set cannot get capture groups
+#[derive(Deref, Debug, Clone, Default)]
+struct RegexSet {
+    #[deref]
+    set: regex::RegexSet,
+    regexes: Vec<regex::Regex>,
+}
+
+impl Serialize for RegexSet {
+    /// Serialize as a list of patterns
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.patterns().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexSet {
+    /// Deserialize from a list of patterns
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Self::new(Vec::<String>::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
 
-#[cfg(test)]
 impl RegexSet {
-    /// Create new RegexSet, currently only needed for tests
+    /// Create new RegexSet
     pub fn new<I, S>(exprs: I) -> Result<Self>
     where
         S: AsRef<str>,
         I: IntoIterator<Item = S>,
     {
-        Ok(RegexSet(regex::RegexSet::new(exprs)?))
+        let set = regex::RegexSet::new(exprs)?;
+        let regexes = set
+            .patterns()
+            .iter()
+            .map(|pattern| regex::Regex::new(pattern))
+            .collect::<Result<_, _>>()?;
+
+        Ok(RegexSet { set, regexes })
+    }
+
+    /// Get the first regex matching a given string
+    fn first_match(&self, haystack: &str) -> Option<&regex::Regex> {
+        self.matches(haystack)
+            .into_iter()
+            .next()
+            .map(|index| &self.regexes[index])
     }
 }
 
@@ -178,19 +521,331 @@ impl Hash for RegexSet {
     }
 }
 
+/// Helper struct for matching local files against glob patterns (e.g. `~/work/**/*.log`)
+/// Keeps the patterns as written so they can be shown and saved unchanged
+#[derive(Debug, Clone, Default)]
+struct GlobSet {
+    patterns: Vec<String>,
+    set: globset::GlobSet,
+}
+
+impl GlobSet {
+    /// Create new GlobSet
+    /// A leading `~` is expanded to the home directory, and patterns that are not absolute
+    /// match at any depth, so `*.log` is the same as `**/*.log`
+    pub fn new(patterns: Vec<String>) -> Result<Self> {
+        let home = std::env::var("HOME").ok();
+        let mut builder = globset::GlobSetBuilder::new();
+
+        for pattern in &patterns {
+            builder.add(
+                globset::GlobBuilder::new(&expand_glob(
+                    pattern,
+                    home.as_deref(),
+                )?)
+                .literal_separator(true)
+                .build()?,
+            );
+        }
+
+        Ok(Self {
+            set: builder.build()?,
+            patterns,
+        })
+    }
+
+    /// Check if there are no patterns
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Get the patterns that match a given path
+    /// Only local files can match, which are compared by their canonicalized absolute path
+    fn matching_patterns(&self, path: &UserPath) -> Vec<&str> {
+        let UserPath::File(file) = path else {
+            return Vec::new();
+        };
+
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        // Paths that do not exist cannot be canonicalized, but can still be matched
+        // once they are made absolute and `.` and `..` are resolved
+        let Ok(file) = std::fs::canonicalize(file).or_else(|_| {
+            std::path::absolute(file).map(|file| normalize_lexically(&file))
+        }) else {
+            return Vec::new();
+        };
+
+        self.set
+            .matches(file)
+            .into_iter()
+            .map(|index| self.patterns[index].as_str())
+            .collect_vec()
+    }
+}
+
+impl Serialize for GlobSet {
+    /// Serialize as a list of patterns
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.patterns.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GlobSet {
+    /// Deserialize from a list of patterns
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        GlobSet::new(Vec::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq for GlobSet {
+    #[mutants::skip] // Trivial
+    fn eq(&self, other: &Self) -> bool {
+        self.patterns == other.patterns
+    }
+}
+
+impl Eq for GlobSet {}
+
+impl Hash for GlobSet {
+    #[mutants::skip] // Trivial
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.patterns.hash(state);
+    }
+}
+
+/// Helper function to resolve `.` and `..` in a path without accessing the filesystem
+/// Unlike canonicalizing, this does not follow symlinks, so `a/link/..` becomes `a`
+fn normalize_lexically(path: &Path) -> PathBuf {
+    path.components()
+        .fold(PathBuf::new(), |mut normalized, component| {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+            normalized
+        })
+}
+
+/// Helper function to expand a leading `~` in a glob pattern to the given home directory
+/// and make patterns that are not absolute match at any depth
+fn expand_glob(pattern: &str, home: Option<&str>) -> Result<String> {
+    let expanded = match pattern.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            // Without a home directory, `~` would silently match `~` at any depth
+            let home =
+                home.ok_or_else(|| Error::BadPath(pattern.to_owned()))?;
+            format!("{}{}", home.trim_end_matches('/'), rest)
+        }
+        _ if pattern.starts_with('/') || pattern.starts_with("**") => {
+            pattern.to_owned()
+        }
+        _ => format!("**/{}", pattern),
+    };
+
+    Ok(expanded)
+}
+
 /// A collection of all of the defined RegexHandlers
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct RegexApps(Vec<RegexHandler>);
 
 impl RegexApps {
-    /// Get a handler matching a given path
-    pub fn get_handler(&self, path: &UserPath) -> Result<RegexHandler> {
-        Ok(self
-            .0
+    /// Iterate over every regex handler, in order of precedence
+    /// Handlers are sorted by descending priority, and otherwise keep the order they are defined in
+    pub fn iter(&self) -> impl Iterator<Item = &RegexHandler> {
+        self.0
             .iter()
-            .find(|app| app.is_match(&path.to_string()))
-            .ok_or_else(|| Error::NotFound(path.to_string()))?
-            .clone())
+            .sorted_by_key(|handler| std::cmp::Reverse(handler.priority))
+    }
+
+    /// Get every handler matching a given path, in order of precedence
+    /// Every handler is tested, and the results are recorded in the given trace
+    pub fn get_handlers(
+        &self,
+        path: &UserPath,
+        trace: &mut Trace,
+    ) -> Vec<RegexHandler> {
+        self.iter()
+            .filter(|app| {
+                let test = app.test(path);
+                let is_match = test.is_match();
+                trace.regex_handlers.push(test);
+                is_match
+            })
+            .cloned()
+            .collect_vec()
+    }
+
+    /// Set the priority of the handler with the given command
+    ///
+    /// Returns `None` if there is no such handler
+    pub fn set_priority(&mut self, exec: &str, priority: i64) -> Option<()> {
+        let handler = self.0.iter_mut().find(|handler| handler.exec == exec)?;
+        handler.priority = priority;
+        debug!("Set priority of `{}` to {}", exec, priority);
+        Some(())
+    }
+
+    /// Set the regexes of the handler with the given command, overwriting any existing ones
+    /// If there is no such handler, it is added
+    pub fn set_handler(
+        &mut self,
+        exec: &str,
+        terminal: bool,
+        regexes: &[String],
+    ) -> Result<()> {
+        let regexes = RegexSet::new(regexes)?;
+
+        match self.0.iter_mut().find(|handler| handler.exec == exec) {
+            Some(handler) => {
+                handler.terminal = terminal;
+                handler.regexes = regexes;
+            }
+            None => self.0.push(RegexHandler {
+                exec: exec.to_owned(),
+                terminal,
+                regexes,
+                ..Default::default()
+            }),
+        }
+
+        debug!("Set regexes for `{}`: {:?}", exec, self.patterns(exec));
+        Ok(())
+    }
+
+    /// Add regexes to the handler with the given command, keeping any existing ones
+    /// If there is no such handler, it is added
+    /// The handler's terminal flag is only changed if one is given
+    pub fn add_handler(
+        &mut self,
+        exec: &str,
+        terminal: Option<bool>,
+        regexes: &[String],
+    ) -> Result<()> {
+        let Some(handler) =
+            self.0.iter_mut().find(|handler| handler.exec == exec)
+        else {
+            return self.set_handler(
+                exec,
+                terminal.unwrap_or_default(),
+                regexes,
+            );
+        };
+
+        if let Some(terminal) = terminal {
+            handler.terminal = terminal;
+        }
+        handler.regexes =
+            RegexSet::new(handler.regexes.patterns().iter().chain(
+                regexes.iter().filter(|regex| {
+                    !handler.regexes.patterns().contains(regex)
+                }),
+            ))?;
+
+        debug!("New regexes for `{}`: {:?}", exec, self.patterns(exec));
+        Ok(())
+    }
+
+    /// Remove regexes from the handler with the given command
+    /// If no regexes are given, or it has no regexes or globs left, the whole handler is removed
+    pub fn remove_handler(
+        &mut self,
+        exec: &str,
+        regexes: &[String],
+    ) -> Result<Option<()>> {
+        let Some(pos) = self.0.iter().position(|handler| handler.exec == exec)
+        else {
+            return Ok(None);
+        };
+
+        let remaining = self.0[pos]
+            .regexes
+            .patterns()
+            .iter()
+            .filter(|regex| !regexes.is_empty() && !regexes.contains(regex))
+            .cloned()
+            .collect_vec();
+
+        if regexes.is_empty()
+            || (remaining.is_empty() && self.0[pos].globs.is_empty())
+        {
+            self.0.remove(pos);
+            debug!("Removed regex handler `{}`", exec);
+        } else {
+            self.0[pos].regexes = RegexSet::new(remaining)?;
+            debug!("New regexes for `{}`: {:?}", exec, self.patterns(exec));
+        }
+
+        Ok(Some(()))
+    }
+
+    /// Helper function to get the regexes of the handler with the given command for logging
+    fn patterns(&self, exec: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|handler| handler.exec == exec)
+            .flat_map(|handler| handler.regexes.patterns())
+            .map(String::as_str)
+            .collect_vec()
+    }
+}
+
+/// Internal helper struct for turning RegexApps into tabular data
+#[derive(Tabled, Serialize)]
+pub struct RegexHandlerEntry {
+    exec: String,
+    terminal: bool,
+    priority: i64,
+    #[tabled(display_with("Self::display_regexes", self))]
+    regexes: Vec<String>,
+    #[tabled(display_with("Self::display_globs", self))]
+    globs: Vec<String>,
+    #[tabled(skip)]
+    #[serde(skip_serializing)]
+    // This field should not appear in any output
+    // It is only used for determining how to render output
+    separator: String,
+}
+
+impl RegexHandlerEntry {
+    /// Create a list of `RegexHandlerEntry`s from every regex handler, in order of precedence
+    pub fn from_apps(apps: &RegexApps, terminal_output: bool) -> Vec<Self> {
+        // If output is a terminal, optimize for readability
+        // Otherwise, if piped, optimize for parseability
+        let separator = if terminal_output { ",\n" } else { ", " };
+
+        apps.iter()
+            .map(|handler| Self {
+                exec: handler.exec.clone(),
+                terminal: handler.terminal,
+                priority: handler.priority,
+                regexes: handler.regexes.patterns().to_vec(),
+                globs: handler.globs.patterns.clone(),
+                separator: separator.to_owned(),
+            })
+            .collect()
+    }
+
+    /// Display list of regexes as a string
+    fn display_regexes(&self) -> String {
+        self.regexes.join(&self.separator)
+    }
+
+    /// Display list of globs as a string
+    fn display_globs(&self) -> String {
+        self.globs.join(&self.separator)
     }
 }
 
@@ -200,38 +855,390 @@ mod tests {
     use crate::common::DesktopEntry;
     use url::Url;
 
+    // Helper function to get the regex handler with the highest precedence matching a given path
+    fn first_handler(
+        regex_apps: &RegexApps,
+        path: &UserPath,
+    ) -> Result<RegexHandler> {
+        regex_apps
+            .get_handlers(path, &mut Trace::default())
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotFound(path.to_string()))
+    }
+
     #[test]
     fn regex_handlers() -> Result<()> {
         let exec: &str = "freetube %u";
-        let regexes: &[String] =
-            &[String::from(r"(https://)?(www\.)?youtu(be\.com|\.be)/*")];
-
-        let regex_handler = RegexHandler {
-            exec: String::from(exec),
-            terminal: false,
-            regexes: RegexSet::new(regexes)?,
-        };
+        let regex_handler: RegexHandler =
+            serde_json::from_value(serde_json::json!({
+                "exec": exec,
+                "regexes": [r"(https://)?(www\.)?youtu(be\.com|\.be)/*"],
+                "env": { "MOZ_ENABLE_WAYLAND": "1" }
+            }))?;
 
         let regex_apps = RegexApps(vec![regex_handler.clone()]);
 
         assert_eq!(
-            regex_apps
-                .get_handler(&UserPath::Url(Url::parse(
-                    "https://youtu.be/dQw4w9WgXcQ"
-                )?))?
-                .get_entry()?,
+            first_handler(
+                &regex_apps,
+                &UserPath::Url(Url::parse("https://youtu.be/dQw4w9WgXcQ")?)
+            )?
+            .get_entry()?,
             DesktopEntry {
                 exec: exec.to_string(),
                 terminal: false,
+                env: BTreeMap::from([(
+                    "MOZ_ENABLE_WAYLAND".into(),
+                    "1".into()
+                )]),
                 ..Default::default()
             }
         );
 
-        assert!(regex_apps
-            .get_handler(&UserPath::Url(Url::parse(
-                "https://en.wikipedia.org",
-            )?))
-            .is_err());
+        assert!(first_handler(
+            &regex_apps,
+            &UserPath::Url(Url::parse("https://en.wikipedia.org",)?)
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_filters() -> Result<()> {
+        let regex_apps: RegexApps =
+            serde_json::from_value(serde_json::json!([
+                {
+                    "exec": "less %f",
+                    "regexes": [".*"],
+                    "mimes": ["text/*"],
+                    "kind": "file"
+                },
+                {
+                    "exec": "firefox %u",
+                    "regexes": [r"example\.com"],
+                    "schemes": ["https"]
+                },
+                { "exec": "nnn %f", "regexes": [".*"], "kind": "directory" },
+            ]))?;
+
+        let handler = |path| -> Result<String> {
+            Ok(first_handler(&regex_apps, &UserPath::from_str(path)?)
+                .map_or_else(|_| "none".into(), |handler| handler.exec))
+        };
+
+        assert_eq!(handler("tests/assets/empty.txt")?, "less %f");
+        assert_eq!(handler("tests/assets/p.html")?, "less %f");
+        assert_eq!(handler("tests/assets")?, "nnn %f");
+        assert_eq!(handler("https://example.com")?, "firefox %u");
+        assert_eq!(handler("http://example.com")?, "none");
+        // Other URLs are not files, even if their mime would match
+        assert_eq!(handler("https://duckduckgo.com")?, "none");
+
+        let filter_mismatch = |index: usize, path| -> Result<Option<String>> {
+            Ok(regex_apps.0[index].filter_mismatch(&UserPath::from_str(path)?))
+        };
+        assert_eq!(
+            filter_mismatch(0, "tests/assets/cmus.desktop")?,
+            Some("mime `application/x-desktop` is not allowed".into())
+        );
+        assert_eq!(
+            filter_mismatch(0, "tests/assets")?,
+            Some("path is a directory, not a file".into())
+        );
+        assert_eq!(
+            filter_mismatch(1, "http://example.com")?,
+            Some("scheme `http` is not allowed".into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_priority() -> Result<()> {
+        let mut regex_apps: RegexApps =
+            serde_json::from_value(serde_json::json!([
+                { "exec": "firefox %u", "regexes": [r"example\.com"] },
+                { "exec": "mpv %u", "regexes": [r"example\.com/watch"] },
+                {
+                    "exec": "freetube %u",
+                    "regexes": [r"example\.com/watch"],
+                    "priority": 10
+                },
+                { "exec": "curl %u", "regexes": [".*"], "priority": -1 },
+            ]))?;
+
+        let handlers = |regex_apps: &RegexApps, path| -> Result<Vec<String>> {
+            Ok(regex_apps
+                .get_handlers(&UserPath::from_str(path)?, &mut Trace::default())
+                .into_iter()
+                .map(|handler| handler.exec)
+                .collect_vec())
+        };
+
+        // Higher priorities come first, ties are broken by config order
+        assert_eq!(
+            handlers(&regex_apps, "https://example.com/watch")?,
+            ["freetube %u", "firefox %u", "mpv %u", "curl %u"]
+        );
+        assert_eq!(
+            handlers(&regex_apps, "https://example.com")?,
+            ["firefox %u", "curl %u"]
+        );
+        assert_eq!(
+            first_handler(
+                &regex_apps,
+                &UserPath::from_str("https://example.com/watch")?
+            )?
+            .exec,
+            "freetube %u"
+        );
+
+        assert_eq!(regex_apps.set_priority("mpv %u", 20), Some(()));
+        assert_eq!(regex_apps.set_priority("feh %f", 20), None);
+        assert_eq!(
+            handlers(&regex_apps, "https://example.com/watch")?,
+            ["mpv %u", "freetube %u", "firefox %u", "curl %u"]
+        );
+
+        // The default priority is not written out
+        assert_eq!(
+            serde_json::to_value(&regex_apps)?[0],
+            serde_json::json!({
+                "exec": "firefox %u",
+                "regexes": [r"example\.com"]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_globs() -> Result<()> {
+        let home = std::env::var("HOME").expect("HOME should be set");
+        let mut regex_apps: RegexApps =
+            serde_json::from_value(serde_json::json!([
+                { "exec": "less %f", "globs": ["tests/assets/*.html"] },
+                { "exec": "nvim %f", "globs": ["~/work/**/*.log"] },
+                { "exec": "glow %f", "globs": ["~/handlr-notes/*.md"] },
+                {
+                    "exec": "feh %f",
+                    "regexes": [r"\.png$"],
+                    "globs": ["/tmp/**"]
+                },
+            ]))?;
+
+        let handler = |regex_apps: &RegexApps, path: &str| -> Result<String> {
+            Ok(first_handler(regex_apps, &UserPath::from_str(path)?)
+                .map_or_else(|_| "none".into(), |handler| handler.exec))
+        };
+
+        // Relative globs match at any depth, but `*` does not cross directories
+        assert_eq!(handler(&regex_apps, "tests/assets/p.html")?, "less %f");
+        assert_eq!(handler(&regex_apps, "tests/assets/a/p.html")?, "none");
+        // Paths are made absolute, whether or not they exist
+        assert_eq!(
+            handler(&regex_apps, &format!("{home}/work/a/b/c.log"))?,
+            "nvim %f"
+        );
+        // `.` and `..` are resolved even if the path does not exist
+        assert_eq!(
+            handler(
+                &regex_apps,
+                &format!("{home}/handlr-notes/../handlr-notes/a.md")
+            )?,
+            "glow %f"
+        );
+        assert_eq!(
+            handler(&regex_apps, &format!("{home}/handlr-notes/./b/../a.md"))?,
+            "glow %f"
+        );
+        assert_eq!(
+            handler(
+                &regex_apps,
+                &format!("{home}/other/../handlr-notes/b/a.md")
+            )?,
+            "none"
+        );
+        assert_eq!(handler(&regex_apps, "/tmp/x/y.txt")?, "feh %f");
+        assert_eq!(handler(&regex_apps, "image.png")?, "feh %f");
+        // Globs never match URLs
+        assert_eq!(handler(&regex_apps, "https://example.com/p.html")?, "none");
+
+        assert_eq!(
+            regex_apps.0[1].matching_patterns(&UserPath::from_str(&format!(
+                "{home}/work/c.log"
+            ))?),
+            ["~/work/**/*.log"]
+        );
+
+        // Globs are written back as they were given
+        assert_eq!(
+            serde_json::to_value(&regex_apps)?[1],
+            serde_json::json!({
+                "exec": "nvim %f",
+                "globs": ["~/work/**/*.log"]
+            })
+        );
+
+        // Handlers with globs are kept when their last regex is removed
+        regex_apps.remove_handler("feh %f", &[r"\.png$".into()])?;
+        assert_eq!(handler(&regex_apps, "/tmp/x/y.txt")?, "feh %f");
+        assert_eq!(handler(&regex_apps, "image.png")?, "none");
+
+        assert!(serde_json::from_value::<RegexApps>(serde_json::json!([
+            { "exec": "less %f", "globs": ["a/{b"] }
+        ]))
+        .is_err());
+
+        // Handlers that could never match anything are rejected
+        assert_eq!(
+            serde_json::from_value::<RegexApps>(serde_json::json!([
+                { "exec": "less %f", "mimes": ["text/*"] }
+            ]))
+            .map_err(|e| e.to_string()),
+            Err("Regex handler 'less %f' has no regexes or globs".to_string())
+        );
+
+        let expand = |pattern, home| {
+            expand_glob(pattern, home).map_err(|e| e.to_string())
+        };
+        assert_eq!(expand("~", Some(&home)), Ok(home.clone()));
+        assert_eq!(
+            expand("~/a/*.txt", Some("/home/user/")),
+            Ok("/home/user/a/*.txt".into())
+        );
+        assert_eq!(expand("/a/*.txt", Some(&home)), Ok("/a/*.txt".into()));
+        assert_eq!(expand("**/*.txt", Some(&home)), Ok("**/*.txt".into()));
+        assert_eq!(expand("*.txt", Some(&home)), Ok("**/*.txt".into()));
+        assert_eq!(
+            expand("~user/*.txt", Some(&home)),
+            Ok("**/~user/*.txt".into())
+        );
+
+        // Only patterns starting with `~` or `~/` need the home directory
+        assert_eq!(
+            expand("~/a/*.txt", None),
+            Err("Bad path: ~/a/*.txt".into())
+        );
+        assert_eq!(expand("~", None), Err("Bad path: ~".into()));
+        assert_eq!(expand("~user/*.txt", None), Ok("**/~user/*.txt".into()));
+        assert_eq!(expand("*.txt", None), Ok("**/*.txt".into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_captures() -> Result<()> {
+        let regex_handler: RegexHandler =
+            serde_json::from_value(serde_json::json!({
+                "exec": "mpv https://youtube.com/watch?v={id}",
+                "regexes": [
+                    r"https://invidious\.\w+/watch\?v=(?P<id>[\w-]+)",
+                    r"https://youtu\.be/(?P<id>[\w-]+)",
+                ]
+            }))?;
+        let config = Config::default();
+
+        // Each path gets its own command, and is not passed on otherwise
+        assert_eq!(
+            regex_handler.open_cmds(
+                &config,
+                vec![
+                    "https://invidious.example/watch?v=dQw4w9WgXcQ".into(),
+                    "https://youtu.be/jNQXAC9IVRw".into(),
+                ]
+            )?,
+            [
+                ["mpv", "https://youtube.com/watch?v=dQw4w9WgXcQ"],
+                ["mpv", "https://youtube.com/watch?v=jNQXAC9IVRw"],
+            ]
+        );
+
+        let regex_handler: RegexHandler =
+            serde_json::from_value(serde_json::json!({
+                "exec": r#"echo "{1} {nope}" {0} %U"#,
+                "regexes": [r"^https://(\w+)\.example/", "^other:"]
+            }))?;
+
+        // Numbered groups are supported, captured text is kept as-is within its argument,
+        // and placeholders that do not name a group are left alone
+        assert_eq!(
+            regex_handler.open_cmds(
+                &config,
+                vec![
+                    "https://a.example/%20".into(),
+                    "other:1".into(),
+                    "other:2".into(),
+                ]
+            )?,
+            [
+                vec![
+                    "echo",
+                    "a {nope}",
+                    "https://a.example/",
+                    "https://a.example/%20"
+                ],
+                vec!["echo", "{1} {nope}", "other:", "other:1"],
+                vec!["echo", "{1} {nope}", "other:", "other:2"],
+            ]
+        );
+
+        // Paths are still opened together when no capture groups are used
+        let regex_handler = RegexHandler {
+            exec: String::from("echo {1} %U"),
+            ..regex_handler
+        };
+        assert_eq!(
+            regex_handler
+                .open_cmds(&config, vec!["other:1".into(), "other:2".into()])?,
+            [["echo", "{1}", "other:1", "other:2"]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn modify_regex_handlers() -> Result<()> {
+        let mut regex_apps = RegexApps::default();
+
+        regex_apps.set_handler("mpv %u", false, &["a".into(), "b".into()])?;
+        regex_apps.add_handler(
+            "mpv %u",
+            Some(true),
+            &["b".into(), "c".into()],
+        )?;
+        assert_eq!(regex_apps.patterns("mpv %u"), ["a", "b", "c"]);
+        assert!(regex_apps.0[0].terminal);
+
+        // Adding only changes the terminal flag if one is given
+        regex_apps.add_handler("mpv %u", None, &["c".into()])?;
+        assert!(regex_apps.0[0].terminal);
+        regex_apps.add_handler("mpv %u", Some(false), &["c".into()])?;
+        assert!(!regex_apps.0[0].terminal);
+
+        // Setting replaces the regexes and terminal flag
+        regex_apps.set_handler("mpv %u", false, &["d".into()])?;
+        assert_eq!(regex_apps.patterns("mpv %u"), ["d"]);
+        assert!(!regex_apps.0[0].terminal);
+
+        regex_apps.add_handler("feh %f", None, &["e".into(), "f".into()])?;
+        assert_eq!(
+            regex_apps.remove_handler("feh %f", &["e".into()])?,
+            Some(())
+        );
+        assert_eq!(regex_apps.patterns("feh %f"), ["f"]);
+
+        // Removing the last regex removes the handler
+        regex_apps.remove_handler("feh %f", &["f".into()])?;
+        assert_eq!(regex_apps.0.len(), 1);
+
+        // Removing without regexes removes the whole handler
+        regex_apps.remove_handler("mpv %u", &[])?;
+        assert!(regex_apps.0.is_empty());
+
+        assert_eq!(regex_apps.remove_handler("mpv %u", &[])?, None);
 
         Ok(())
     }
@@ -0,0 +1,262 @@
+use itertools::Itertools;
+use std::fmt::Display;
+
+/// A format-preserving representation of an INI-style file, such as mimeapps.list
+///
+/// Only entries that are explicitly changed are rewritten.
+/// Everything else (comments, blank lines, unknown groups, key order, etc.) is kept as-is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IniDocument {
+    lines: Vec<Line>,
+    /// Whether the last line is terminated by a newline
+    trailing_newline: bool,
+}
+
+/// A single line of an INI-style file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Group header, e.g. `[Default Applications]`
+    Group { raw: String, name: String },
+    /// Key-value pair, e.g. `text/plain=nvim.desktop;`
+    Entry {
+        raw: String,
+        key: String,
+        value: String,
+    },
+    /// Comments, blank lines and anything else that is not understood
+    Other(String),
+}
+
+impl Line {
+    /// Parse a single line
+    fn parse(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        let raw = raw.to_owned();
+
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            Self::Other(raw)
+        } else if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            Self::Group {
+                name: name.to_owned(),
+                raw,
+            }
+        } else if let Some((key, value)) = trimmed.split_once('=') {
+            Self::Entry {
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+                raw,
+            }
+        } else {
+            Self::Other(raw)
+        }
+    }
+
+    /// Create a new key-value pair
+    fn entry(key: &str, value: &str) -> Self {
+        Self::Entry {
+            raw: format!("{key}={value}"),
+            key: key.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    /// Get the raw text of the line
+    fn raw(&self) -> &str {
+        match self {
+            Self::Group { raw, .. } | Self::Entry { raw, .. } => raw,
+            Self::Other(raw) => raw,
+        }
+    }
+}
+
+impl Default for IniDocument {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            trailing_newline: true,
+        }
+    }
+}
+
+impl IniDocument {
+    /// Parse an INI-style file
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(Line::parse).collect_vec(),
+            trailing_newline: text.is_empty() || text.ends_with('\n'),
+        }
+    }
+
+    /// Get the indices of every line that belongs to a given group, including the group header
+    /// A group may be split across multiple headers with the same name
+    fn group_lines(&self, group: &str) -> Vec<usize> {
+        let mut in_group = false;
+
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                if let Line::Group { name, .. } = line {
+                    in_group = name == group;
+                }
+                in_group
+            })
+            .map(|(index, _)| index)
+            .collect_vec()
+    }
+
+    /// Get every key-value pair in a given group, in order
+    pub fn entries(&self, group: &str) -> Vec<(&str, &str)> {
+        self.group_lines(group)
+            .into_iter()
+            .filter_map(|index| match &self.lines[index] {
+                Line::Entry { key, value, .. } => {
+                    Some((key.as_str(), value.as_str()))
+                }
+                _ => None,
+            })
+            .collect_vec()
+    }
+
+    /// Get the value of a given key in a given group
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.entries(group)
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Set the value of a given key in a given group
+    ///
+    /// If the key already exists, only its line is rewritten and any duplicates are removed.
+    /// Otherwise, it is inserted into the group, keeping it sorted if it already was.
+    /// If the group does not exist, it is created at the end of the file.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let entries = self
+            .group_lines(group)
+            .into_iter()
+            .filter_map(|index| match &self.lines[index] {
+                Line::Entry { key, .. } => Some((index, key.clone())),
+                _ => None,
+            })
+            .collect_vec();
+
+        let mut existing = entries
+            .iter()
+            .filter(|(_, k)| k == key)
+            .map(|(index, _)| *index);
+
+        if let Some(first) = existing.next() {
+            // Remove duplicates back to front so indices stay valid
+            existing.collect_vec().into_iter().rev().for_each(|index| {
+                self.lines.remove(index);
+            });
+            self.lines[first] = Line::entry(key, value);
+            return;
+        }
+
+        let is_sorted = entries.windows(2).all(|pair| pair[0].1 <= pair[1].1);
+
+        let index = match entries.last() {
+            // Insert right after the header of an empty group
+            None => self.group_lines(group).first().map(|header| header + 1),
+            Some((last, _)) if is_sorted => Some(
+                entries
+                    .iter()
+                    .find(|(_, k)| k.as_str() > key)
+                    .map_or(last + 1, |(index, _)| *index),
+            ),
+            Some((last, _)) => Some(last + 1),
+        };
+
+        match index {
+            Some(index) => self.lines.insert(index, Line::entry(key, value)),
+            None => {
+                self.lines.push(Line::Group {
+                    raw: format!("[{group}]"),
+                    name: group.to_owned(),
+                });
+                self.lines.push(Line::entry(key, value));
+            }
+        }
+    }
+
+    /// Remove a given key from a given group
+    pub fn remove(&mut self, group: &str, key: &str) {
+        let group_lines = self.group_lines(group);
+
+        group_lines.into_iter().rev().for_each(|index| {
+            if matches!(&self.lines[index], Line::Entry { key: k, .. } if k == key)
+            {
+                self.lines.remove(index);
+            }
+        });
+    }
+}
+
+impl Display for IniDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lines.iter().map(Line::raw).join("\n"))?;
+
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str("\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    const TEXT: &str = "# Comment
+[Group]
+b=2
+c=3
+
+[Other Group]
+; Another comment
+z=26
+";
+
+    #[test]
+    fn ini_round_trip() {
+        assert_eq!(IniDocument::parse(TEXT).to_string(), TEXT);
+        assert_eq!(IniDocument::parse("[Group]").to_string(), "[Group]");
+    }
+
+    #[test]
+    fn ini_edit() {
+        let mut document = IniDocument::parse(TEXT);
+
+        assert_eq!(document.get("Group", "b"), Some("2"));
+        assert_eq!(document.get("Other Group", "b"), None);
+
+        document.set("Group", "a", "1");
+        document.set("Group", "c", "three");
+        document.set("Group", "d", "4");
+        document.remove("Other Group", "z");
+        document.set("New Group", "y", "25");
+
+        assert_eq!(
+            document.to_string(),
+            "# Comment
+[Group]
+a=1
+b=2
+c=three
+d=4
+
+[Other Group]
+; Another comment
+[New Group]
+y=25
+"
+        );
+    }
+}
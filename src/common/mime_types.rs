@@ -1,6 +1,7 @@
 use crate::error::{Error, Result};
-use derive_more::Deref;
+use derive_more::{Deref, Display};
 use mime::Mime;
+use serde::Serialize;
 use std::{convert::TryFrom, path::Path, str::FromStr};
 use tracing_unwrap::{OptionExt, ResultExt};
 use url::Url;
@@ -9,7 +10,62 @@ use url::Url;
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Deref)]
 pub struct MimeType(pub Mime);
 
+/// How a mime was determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MimeSource {
+    /// Given directly as a mime
+    #[display(fmt = "given directly")]
+    Given,
+    /// Looked up from a file extension
+    #[display(fmt = "file extension")]
+    Extension,
+    /// Derived from a URL's scheme
+    #[display(fmt = "URL scheme")]
+    UrlScheme,
+    /// Guessed from a file's name
+    #[display(fmt = "file name")]
+    FileName,
+    /// Guessed from a file's contents
+    #[display(fmt = "file contents")]
+    FileContents,
+}
+
 impl MimeType {
+    /// Guess the mime of a given path, along with whether it was guessed from its name or its contents
+    pub fn from_path(path: &Path) -> (Self, MimeSource) {
+        let db = xdg_mime::SharedMimeInfo::new();
+
+        let mut guess = db.guess_mime_type();
+        guess.file_name(&path.to_string_lossy());
+
+        let mime = guess.guess().mime_type().clone();
+        // TODO: remove this check once xdg-mime crate makes a new release (currently v0.4.0)
+        if mime
+            == "application/x-zerosize"
+                .parse::<Mime>()
+                .expect_or_log("Hardcoded mime should be valid")
+        {
+            (
+                Self(guess.path(path).guess().mime_type().clone()),
+                MimeSource::FileContents,
+            )
+        } else {
+            (Self(mime), MimeSource::FileName)
+        }
+    }
+
+    /// Parse a mime or file extension given by the user, along with which of the two it was
+    pub fn parse_with_source(s: &str) -> Result<(Self, MimeSource)> {
+        let source = if s.starts_with('.') {
+            MimeSource::Extension
+        } else {
+            MimeSource::Given
+        };
+
+        Ok((Self::from_str(s)?, source))
+    }
+
     /// Gets the `Mime` from a given file extension
     fn from_ext(ext: &str) -> Result<Mime> {
         match xdg_mime::SharedMimeInfo::new()
@@ -40,22 +96,7 @@ impl TryFrom<&Url> for MimeType {
 impl TryFrom<&Path> for MimeType {
     type Error = Error;
     fn try_from(path: &Path) -> Result<Self> {
-        let db = xdg_mime::SharedMimeInfo::new();
-
-        let mut guess = db.guess_mime_type();
-        guess.file_name(&path.to_string_lossy());
-
-        let mut mime = guess.guess().mime_type().clone();
-        // TODO: remove this check once xdg-mime crate makes a new release (currently v0.4.0)
-        if mime
-            == "application/x-zerosize"
-                .parse::<Mime>()
-                .expect_or_log("Hardcoded mime should be valid")
-        {
-            mime = guess.path(path).guess().mime_type().clone();
-        }
-
-        Ok(Self(mime.clone()))
+        Ok(Self::from_path(path).0)
     }
 }
 
@@ -139,6 +180,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mime_source() -> Result<()> {
+        assert_eq!(
+            MimeType::from_path(Path::new("./tests/assets/p.html")).1,
+            MimeSource::FileName
+        );
+        assert_eq!(
+            MimeType::from_path(Path::new("./tests/assets/cat")).1,
+            MimeSource::FileContents
+        );
+        assert_eq!(
+            MimeType::parse_with_source(".mp3")?.1,
+            MimeSource::Extension
+        );
+        assert_eq!(
+            MimeType::parse_with_source("audio/mpeg")?.1,
+            MimeSource::Given
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn from_str() -> Result<()> {
         assert_eq!(".mp3".parse::<MimeType>()?.0, "audio/mpeg");
@@ -1,15 +1,22 @@
 mod db;
+mod dbus;
 mod desktop_entry;
+mod exec;
 mod handler;
+mod ini;
 mod mime_types;
 mod path;
+mod rewrite;
 mod table;
 
 pub use self::db::mime_types;
-pub use desktop_entry::{DesktopEntry, Mode as ExecMode};
+pub use desktop_entry::{current_desktops, DesktopEntry, Mode as ExecMode};
 pub use handler::{
     DesktopHandler, Handleable, Handler, RegexApps, RegexHandler,
+    RegexHandlerEntry,
 };
-pub use mime_types::MimeType;
-pub use path::{mime_table, UserPath};
+pub use ini::IniDocument;
+pub use mime_types::{MimeSource, MimeType};
+pub use path::{mime_table, MimeOrPath, PathKind, UserPath};
+pub use rewrite::Rewrites;
 pub use table::render_table;
@@ -1,15 +1,16 @@
 use crate::{
-    common::{render_table, MimeType},
+    common::{render_table, MimeSource, MimeType, Rewrites},
     error::{Error, Result},
 };
+use derive_more::Display;
 use itertools::Itertools;
 use mime::Mime;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    convert::{TryFrom, TryInto},
+    convert::TryFrom,
     fmt::{Display, Formatter},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 use tabled::Tabled;
@@ -22,13 +23,56 @@ pub enum UserPath {
     File(PathBuf),
 }
 
+/// The kinds of paths that can be given by the user
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Display, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PathKind {
+    /// A local file that is not a directory
+    #[display(fmt = "file")]
+    File,
+    /// A local directory
+    #[display(fmt = "directory")]
+    Directory,
+    /// A URL with any scheme other than `file`
+    #[display(fmt = "url")]
+    Url,
+}
+
 impl UserPath {
+    /// Get the kind of path this is
+    pub fn kind(&self) -> PathKind {
+        match self {
+            Self::Url(_) => PathKind::Url,
+            Self::File(f) if f.is_dir() => PathKind::Directory,
+            Self::File(_) => PathKind::File,
+        }
+    }
+
+    /// Get the URL scheme of the path, where local files have the `file` scheme
+    pub fn scheme(&self) -> &str {
+        match self {
+            Self::Url(url) => url.scheme(),
+            Self::File(_) => "file",
+        }
+    }
+
     pub fn get_mime(&self) -> Result<Mime> {
+        Ok(self.get_mime_with_source()?.0)
+    }
+
+    /// Get the mime of the path/URL, along with how it was determined
+    pub fn get_mime_with_source(&self) -> Result<(Mime, MimeSource)> {
         Ok(match self {
-            Self::Url(url) => Ok(url.try_into()?),
-            Self::File(f) => MimeType::try_from(f.as_path()),
-        }?
-        .0)
+            Self::Url(url) => {
+                (MimeType::try_from(url)?.0, MimeSource::UrlScheme)
+            }
+            Self::File(f) => {
+                let (mime, source) = MimeType::from_path(f);
+                (mime.0, source)
+            }
+        })
     }
 }
 
@@ -60,27 +104,91 @@ impl Display for UserPath {
     }
 }
 
+/// Either a mime/file extension or a path/URL given by the user
+#[derive(Debug, Clone)]
+pub enum MimeOrPath {
+    Mime(MimeType, MimeSource),
+    Path(UserPath),
+}
+
+impl FromStr for MimeOrPath {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // URLs and existing files are always paths,
+        // and anything that is not a valid mime or extension is assumed to be a path
+        if Url::parse(s).is_ok() || Path::new(s).exists() {
+            Ok(Self::Path(UserPath::from_str(s)?))
+        } else if let Ok((mime, source)) = MimeType::parse_with_source(s) {
+            Ok(Self::Mime(mime, source))
+        } else {
+            Ok(Self::Path(UserPath::from_str(s)?))
+        }
+    }
+}
+
+impl Display for MimeOrPath {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Mime(mime, _) => fmt.write_str(mime.essence_str()),
+            Self::Path(path) => path.fmt(fmt),
+        }
+    }
+}
+
 /// Internal helper struct for turning a UserPath into tabular data
 #[derive(Tabled, Serialize)]
 struct UserPathTable {
     path: String,
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rewritten: Option<String>,
     mime: String,
 }
 
 impl UserPathTable {
-    fn new(path: &UserPath) -> Result<Self> {
+    /// Create a row for a path, using the mime of what it is rewritten to, if anything
+    fn new(path: &UserPath, rewrites: &Rewrites) -> Result<Self> {
+        let rewritten = rewrites.apply(path)?;
+
         Ok(Self {
             path: path.to_string(),
-            mime: path.get_mime()?.essence_str().to_owned(),
+            rewritten: rewritten.as_ref().map(UserPath::to_string),
+            mime: rewritten
+                .as_ref()
+                .unwrap_or(path)
+                .get_mime()?
+                .essence_str()
+                .to_owned(),
         })
     }
 }
 
+/// Internal helper struct for showing rewritten paths in tabular data
+/// Only used if any paths have been rewritten
+#[derive(Tabled)]
+struct RewrittenUserPathTable {
+    path: String,
+    rewritten: String,
+    mime: String,
+}
+
+impl From<UserPathTable> for RewrittenUserPathTable {
+    fn from(row: UserPathTable) -> Self {
+        Self {
+            path: row.path,
+            rewritten: row.rewritten.unwrap_or_default(),
+            mime: row.mime,
+        }
+    }
+}
+
 /// Render a table of mime types from a list of paths
 /// and write it to the given writer
+/// If any paths are rewritten, an extra column shows what they are rewritten to
 pub fn mime_table<W: Write>(
     writer: &mut W,
     paths: &[UserPath],
+    rewrites: &Rewrites,
     output_json: bool,
     terminal_output: bool,
 ) -> Result<()> {
@@ -95,11 +203,19 @@ pub fn mime_table<W: Write>(
 
     let rows = paths
         .iter()
-        .map(UserPathTable::new)
+        .map(|path| UserPathTable::new(path, rewrites))
         .collect::<Result<Vec<UserPathTable>>>()?;
 
     let table = if output_json {
         serde_json::to_string(&rows)?
+    } else if rows.iter().any(|row| row.rewritten.is_some()) {
+        render_table(
+            &rows
+                .into_iter()
+                .map(RewrittenUserPathTable::from)
+                .collect_vec(),
+            terminal_output,
+        )
     } else {
         render_table(&rows, terminal_output)
     };
@@ -142,10 +258,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mime_or_path() -> Result<()> {
+        let parse = |s| -> Result<String> {
+            Ok(match MimeOrPath::from_str(s)? {
+                MimeOrPath::Mime(mime, source) => {
+                    format!("mime {} ({source})", *mime)
+                }
+                MimeOrPath::Path(path) => format!("path {path}"),
+            })
+        };
+
+        assert_eq!(parse("text/plain")?, "mime text/plain (given directly)");
+        assert_eq!(parse(".mp3")?, "mime audio/mpeg (file extension)");
+        assert_eq!(
+            parse("https://duckduckgo.com")?,
+            "path https://duckduckgo.com/"
+        );
+        // Existing files take precedence over mimes
+        assert_eq!(parse("tests/assets")?, "path tests/assets");
+        // Anything else is assumed to be a path
+        assert_eq!(parse("nonexistent.txt")?, "path nonexistent.txt");
+
+        Ok(())
+    }
+
     #[test]
     fn mime_table_terminal() -> Result<()> {
         let mut buffer = Vec::new();
-        mime_table(&mut buffer, &paths()?, false, true)?;
+        mime_table(&mut buffer, &paths()?, &Rewrites::default(), false, true)?;
         insta::assert_snapshot!(String::from_utf8(buffer)?);
         Ok(())
     }
@@ -153,8 +294,34 @@ mod tests {
     #[test]
     fn test_mime_table_piped() -> Result<()> {
         let mut buffer = Vec::new();
-        mime_table(&mut buffer, &paths()?, false, false)?;
+        mime_table(&mut buffer, &paths()?, &Rewrites::default(), false, false)?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+        Ok(())
+    }
+
+    #[test]
+    fn mime_table_rewritten() -> Result<()> {
+        let rewrites: Rewrites = serde_json::from_value(serde_json::json!([
+            {
+                "regex": r"^https://(www\.)?reddit\.com/",
+                "replacement": "https://old.reddit.com/"
+            },
+            { "regex": r"^https://", "replacement": "gemini://" },
+            { "regex": r"\.md$", "replacement": ".txt" },
+        ]))?;
+        let paths = ["https://reddit.com/r/rust", "../README.md", "."]
+            .iter()
+            .map(|p| UserPath::from_str(p))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut buffer = Vec::new();
+        mime_table(&mut buffer, &paths, &rewrites, false, false)?;
         insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        let mut buffer = Vec::new();
+        mime_table(&mut buffer, &paths, &rewrites, true, false)?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
         Ok(())
     }
 
@@ -163,12 +330,12 @@ mod tests {
         //NOTE: both calls should have the same result
         // JSON output and terminal output
         let mut buffer = Vec::new();
-        mime_table(&mut buffer, &paths()?, true, true)?;
+        mime_table(&mut buffer, &paths()?, &Rewrites::default(), true, true)?;
         insta::assert_snapshot!(String::from_utf8(buffer)?);
 
         // JSON output and no terminal output
         let mut buffer = Vec::new();
-        mime_table(&mut buffer, &paths()?, true, false)?;
+        mime_table(&mut buffer, &paths()?, &Rewrites::default(), true, false)?;
         insta::assert_snapshot!(String::from_utf8(buffer)?);
 
         Ok(())
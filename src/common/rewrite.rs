@@ -0,0 +1,102 @@
+use crate::{common::UserPath, error::Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// Represents a rule for rewriting paths/URLs from the config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewriteRule {
+    #[serde(with = "serde_regex")]
+    regex: Regex,
+    /// Text to replace matches with, which can refer to capture groups (e.g. `$1` or `${name}`)
+    #[serde(default)]
+    replacement: String,
+}
+
+/// A collection of all of the defined RewriteRules
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rewrites(Vec<RewriteRule>);
+
+impl Rewrites {
+    /// Rewrite a given path/URL with every rule, in order
+    /// Each rule replaces all of its matches in the result of the previous one
+    ///
+    /// Returns `None` if the path/URL is unchanged
+    pub fn apply(&self, path: &UserPath) -> Result<Option<UserPath>> {
+        let original = path.to_string();
+
+        let rewritten = self.0.iter().fold(original.clone(), |path, rule| {
+            let rewritten = rule
+                .regex
+                .replace_all(&path, rule.replacement.as_str())
+                .into_owned();
+
+            if rewritten != path {
+                debug!(
+                    "Rewrite rule `{}` turned `{}` into `{}`",
+                    rule.regex, path, rewritten
+                );
+            }
+
+            rewritten
+        });
+
+        if rewritten == original {
+            Ok(None)
+        } else {
+            info!("Rewrote `{}` to `{}`", original, rewritten);
+            Ok(Some(UserPath::from_str(&rewritten)?))
+        }
+    }
+
+    /// Rewrite every given path/URL, keeping ones that are unchanged as-is
+    pub fn apply_all(&self, paths: &[UserPath]) -> Result<Vec<UserPath>> {
+        paths
+            .iter()
+            .map(|path| Ok(self.apply(path)?.unwrap_or_else(|| path.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn rewrite_paths() -> Result<()> {
+        let rewrites: Rewrites = serde_json::from_value(serde_json::json!([
+            // Strip tracking parameters
+            { "regex": r"([?&])utm_[^&]*&?", "replacement": "$1" },
+            { "regex": r"[?&]$" },
+            { "regex": r"^https://(www\.)?twitter\.com/", "replacement": "https://nitter.net/" },
+            { "regex": r"^https://(www\.)?reddit\.com/", "replacement": "https://old.reddit.com/" },
+            // Rules apply to the result of the previous ones
+            { "regex": r"^https://nitter\.net/(?<user>\w+)$", "replacement": "https://nitter.net/${user}/with_replies" },
+        ]))?;
+
+        let rewrite = |path| -> Result<Option<String>> {
+            Ok(rewrites
+                .apply(&UserPath::from_str(path)?)?
+                .map(|path| path.to_string()))
+        };
+
+        assert_eq!(
+            rewrite("https://example.com/?utm_source=x&id=1&utm_medium=y")?,
+            Some("https://example.com/?id=1".into())
+        );
+        assert_eq!(
+            rewrite("https://www.reddit.com/r/rust?utm_source=share")?,
+            Some("https://old.reddit.com/r/rust".into())
+        );
+        assert_eq!(
+            rewrite("https://twitter.com/rustlang")?,
+            Some("https://nitter.net/rustlang/with_replies".into())
+        );
+        assert_eq!(rewrite("https://example.com/?id=1")?, None);
+        assert_eq!(rewrite("tests/assets/empty.txt")?, None);
+
+        Ok(())
+    }
+}
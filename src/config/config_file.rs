@@ -1,10 +1,14 @@
 use crate::{
     cli::SelectorArgs,
-    common::{RegexApps, RegexHandler, UserPath},
-    error::Result,
+    common::{DesktopHandler, RegexApps, RegexHandler, Rewrites, UserPath},
+    config::Trace,
+    error::{Error, Result},
 };
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf, str::FromStr};
+use toml_edit::{DocumentMut, Item, Table, Value};
+use tracing::{debug, info};
 
 /// The config file
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,14 +18,31 @@ pub struct ConfigFile {
     pub enable_selector: bool,
     /// The selector command to run
     pub selector: String,
+    /// Whether to also run the selector when multiple regex handlers match a path/URL,
+    /// choosing between every matching regex handler and the default handlers for its mime
+    /// Only applies if the selector is enabled
+    pub select_regex_handlers: bool,
     /// Extra arguments to pass to terminal application
     pub term_exec_args: Option<String>,
     /// Whether to expand wildcards when saving mimeapps.list
     pub expand_wildcards: bool,
+    /// Whether to run handlers in their own transient systemd user scope
+    pub systemd_scope: bool,
+    /// How long to watch handlers for after starting them, in milliseconds
+    /// Handlers that exit unsuccessfully within this time have their stderr reported.
+    /// Set to 0 to disable.
+    pub handler_grace_period: u64,
+    /// Whether to try the next handler for a mime if one fails during the grace period
+    pub fallback_on_failure: bool,
     /// Regex handlers
-    // NOTE: Serializing is only necessary for generating a default config file
-    #[serde(skip_serializing)]
     pub handlers: RegexApps,
+    /// Rules for rewriting paths/URLs before opening them, applied in order
+    pub rewrites: Rewrites,
+    /// Extra environment variables to run desktop handlers with, keyed by desktop file
+    pub handler_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// The config file as it was read, so comments and ordering survive saving
+    #[serde(skip)]
+    document: DocumentMut,
 }
 
 impl Default for ConfigFile {
@@ -29,29 +50,187 @@ impl Default for ConfigFile {
         ConfigFile {
             enable_selector: false,
             selector: "rofi -dmenu -i -p 'Open With: '".into(),
+            select_regex_handlers: false,
             // Required for many xterm-compatible terminal emulators
             // Unfortunately, messes up emulators that don't accept it
             term_exec_args: Some("-e".into()),
             expand_wildcards: false,
+            systemd_scope: false,
+            handler_grace_period: 0,
+            fallback_on_failure: false,
+            handler_env: BTreeMap::new(),
             handlers: Default::default(),
+            rewrites: Default::default(),
+            document: DocumentMut::new(),
         }
     }
 }
 
 impl ConfigFile {
-    /// Get the handler associated with a given mime from the config file's regex handlers
-    pub fn get_regex_handler(&self, path: &UserPath) -> Result<RegexHandler> {
-        self.handlers.get_handler(path)
+    /// Get every regex handler matching a given path, in order of precedence
+    /// Every handler is tested, and the results are recorded in the given trace
+    pub fn get_regex_handlers(
+        &self,
+        path: &UserPath,
+        trace: &mut Trace,
+    ) -> Vec<RegexHandler> {
+        self.handlers.get_handlers(path, trace)
+    }
+
+    /// Get the extra environment variables to run a given desktop handler with
+    /// Variables set for a desktop file also apply to its actions,
+    /// but can be overridden for a specific action (e.g. `firefox.desktop:new-private-window`)
+    pub fn get_handler_env(
+        &self,
+        handler: &DesktopHandler,
+    ) -> BTreeMap<String, String> {
+        let (name, action) = handler.split_action();
+
+        std::iter::once(name.to_string_lossy().to_string())
+            .chain(action.map(|_| handler.to_string()))
+            .filter_map(|key| self.handler_env.get(&key))
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
     }
 
     /// Load ~/.config/handlr/handlr.toml
+    /// If it does not exist yet, it is created with the default config
     #[mutants::skip] // Cannot test directly, depends on system state
     pub fn load() -> Result<Self> {
-        Ok(confy::load("handlr")?)
+        let path = Self::path()?;
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => contents.parse(),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("Writing default config to {}", path.display());
+                let config = Self {
+                    document: serialize(&Self::default())?,
+                    ..Default::default()
+                };
+                config.save()?;
+                Ok(config)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save to ~/.config/handlr/handlr.toml
+    #[mutants::skip] // Cannot test directly, alters system state
+    pub fn save(&self) -> Result<()> {
+        if cfg!(test) {
+            Ok(())
+        } else {
+            let path = Self::path()?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Ok(std::fs::write(path, self.to_document()?.to_string())?)
+        }
+    }
+
+    /// Get the path to handlr's config file
+    #[mutants::skip] // Cannot test directly, depends on system state
+    fn path() -> Result<PathBuf> {
+        Ok(xdg::BaseDirectories::new()?
+            .get_config_home()
+            .join("handlr/handlr.toml"))
+    }
+
+    /// Merge the current config into the document it was read from
+    /// Only keys whose values have changed are rewritten,
+    /// so everything else keeps its comments and formatting
+    fn to_document(&self) -> Result<DocumentMut> {
+        let mut document = self.document.clone();
+        let old =
+            serialize(&toml_edit::de::from_document(self.document.clone())?)?;
+        let new = serialize(self)?;
+
+        for key in old
+            .iter()
+            .chain(new.iter())
+            .map(|(key, _)| key.to_owned())
+            .unique()
+        {
+            let new_item = new.get(&key);
+            if display(&key, old.get(&key)) == display(&key, new_item) {
+                continue;
+            }
+
+            debug!("Updating `{}` in config file", key);
+            match new_item {
+                Some(item) => insert_item(&mut document, &key, item.clone()),
+                None => {
+                    document.remove(&key);
+                }
+            }
+        }
+
+        Ok(document)
+    }
+
+    /// Get the value of a key in the config file, or the whole config file if no key is given
+    /// Strings are shown as-is, and everything else is shown as TOML
+    pub fn get(&self, key: Option<&str>) -> Result<String> {
+        let document = serialize(self)?;
+
+        let Some(key) = key else {
+            return Ok(document.to_string());
+        };
+
+        match document.get(key) {
+            Some(Item::Value(Value::String(string))) => {
+                Ok(string.value().to_owned())
+            }
+            Some(Item::Value(value)) => Ok(value.to_string().trim().to_owned()),
+            Some(item) => {
+                let mut table = DocumentMut::new();
+                table.insert(key, item.clone());
+                Ok(table.to_string())
+            }
+            // Optional keys that are not set
+            None if Self::keys()?.contains(&key.to_owned()) => {
+                Ok(String::new())
+            }
+            None => Err(Error::UnknownConfigKey(key.to_owned())),
+        }
+    }
+
+    /// Set the value of a key in the config file
+    /// Values are parsed as TOML, falling back to a plain string if they are not valid TOML
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if !Self::keys()?.contains(&key.to_owned()) {
+            return Err(Error::UnknownConfigKey(key.to_owned()));
+        }
+
+        let mut value = value
+            .parse::<Value>()
+            .unwrap_or_else(|_| Value::from(value));
+        value.decor_mut().clear();
+
+        let mut document = self.document.clone();
+        insert_item(&mut document, key, Item::Value(value));
+
+        // Make sure the new value has the right type before keeping it
+        *self = Self {
+            document: document.clone(),
+            ..toml_edit::de::from_document(document)?
+        };
+
+        debug!("Set `{}` in config file", key);
+        Ok(())
+    }
+
+    /// Get every key that can be set in the config file
+    pub fn keys() -> Result<Vec<String>> {
+        Ok(toml_edit::ser::to_document(&Self::default())?
+            .iter()
+            .map(|(key, _)| key.to_owned())
+            .collect())
     }
 
     /// Override the set selector
-    /// Currently assumes the config file will never be saved to
+    /// Only used by commands that never save the config file
     pub fn override_selector(&mut self, selector_args: SelectorArgs) {
         if let Some(selector) = selector_args.selector {
             debug!("Overriding selector command: {}", selector);
@@ -65,3 +244,270 @@ impl ConfigFile {
         debug!("Selector enabled: {}", self.enable_selector);
     }
 }
+
+impl FromStr for ConfigFile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let document = s.parse::<DocumentMut>()?;
+
+        Ok(Self {
+            document: document.clone(),
+            ..toml_edit::de::from_document(document)?
+        })
+    }
+}
+
+/// Helper function to serialize a config into a document with standard tables
+fn serialize(config: &ConfigFile) -> Result<DocumentMut> {
+    let mut document = toml_edit::ser::to_document(config)?;
+    document
+        .iter_mut()
+        .for_each(|(_, item)| *item = expand_tables(std::mem::take(item)));
+    Ok(document)
+}
+
+/// Helper function to insert an item into a table,
+/// merging it with any existing item under the same key
+fn insert_item(table: &mut Table, key: &str, item: Item) {
+    match table.get_mut(key) {
+        Some(old) => {
+            // Keys are formatted differently for values and table headers
+            let reshaped =
+                std::mem::discriminant(old) != std::mem::discriminant(&item);
+            merge_item(old, item);
+            if let Some(mut key) = table.key_mut(key).filter(|_| reshaped) {
+                key.leaf_decor_mut().clear();
+            }
+        }
+        None => {
+            table.insert(key, item);
+        }
+    }
+}
+
+/// Helper function to merge a new item into an old one
+/// Comments and formatting of the old item are kept wherever its structure is unchanged
+fn merge_item(old: &mut Item, new: Item) {
+    match (old, new) {
+        (Item::Table(old), Item::Table(new)) => merge_table(old, new),
+        (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+            let len = new.len();
+            for (i, table) in new.into_iter().enumerate() {
+                match old.get_mut(i) {
+                    Some(old) => merge_table(old, table),
+                    None => old.push(table),
+                }
+            }
+            while old.len() > len {
+                old.remove(old.len() - 1);
+            }
+        }
+        (Item::Value(old), Item::Value(mut new)) => {
+            if bare(old) != bare(&new) {
+                *new.decor_mut() = old.decor().clone();
+                *old = new;
+            }
+        }
+        (old, new) => *old = new,
+    }
+}
+
+/// Helper function to merge a new table into an old one
+fn merge_table(old: &mut Table, new: Table) {
+    old.retain(|key, _| new.contains_key(key));
+    for (key, item) in new {
+        insert_item(old, &key, item);
+    }
+}
+
+/// Helper function to display an item along with any tables nested in it
+fn display(key: &str, item: Option<&Item>) -> Option<String> {
+    let mut document = DocumentMut::new();
+    document.insert(key, item?.clone());
+    Some(document.to_string())
+}
+
+/// Helper function to display a value without its surrounding whitespace and comments
+fn bare(value: &Value) -> String {
+    let mut value = value.clone();
+    value.decor_mut().clear();
+    value.to_string()
+}
+
+/// Helper function to turn inline tables into standard tables,
+/// since serializing only produces inline ones
+fn expand_tables(item: Item) -> Item {
+    match item {
+        Item::Value(Value::InlineTable(table)) => {
+            let mut table = table.into_table();
+            table.iter_mut().for_each(|(_, value)| {
+                *value = expand_tables(std::mem::take(value))
+            });
+            // Only show headers for tables that have their own values
+            let implicit = table.iter().all(|(_, item)| item.is_table());
+            table.set_implicit(implicit);
+            Item::Table(table)
+        }
+        Item::Value(Value::Array(array))
+            if !array.is_empty()
+                && array.iter().all(Value::is_inline_table) =>
+        {
+            let mut tables = toml_edit::ArrayOfTables::new();
+            array
+                .into_iter()
+                .filter_map(|value| value.as_inline_table().cloned())
+                .for_each(|table| tables.push(table.into_table()));
+            Item::ArrayOfTables(tables)
+        }
+        item => item,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+    use std::str::FromStr;
+
+    #[test]
+    fn handler_env() -> Result<()> {
+        let config = ConfigFile {
+            handler_env: BTreeMap::from([
+                (
+                    "firefox.desktop".into(),
+                    BTreeMap::from([
+                        ("MOZ_ENABLE_WAYLAND".into(), "1".into()),
+                        ("MOZ_DBUS_REMOTE".into(), "1".into()),
+                    ]),
+                ),
+                (
+                    "firefox.desktop:new-private-window".into(),
+                    BTreeMap::from([("MOZ_DBUS_REMOTE".into(), "0".into())]),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let env = |handler| -> Result<Vec<(String, String)>> {
+            Ok(config
+                .get_handler_env(&DesktopHandler::from_str(handler)?)
+                .into_iter()
+                .collect())
+        };
+
+        assert_eq!(
+            env("firefox.desktop")?,
+            vec![
+                ("MOZ_DBUS_REMOTE".into(), "1".into()),
+                ("MOZ_ENABLE_WAYLAND".into(), "1".into())
+            ]
+        );
+        assert_eq!(
+            env("firefox.desktop:new-private-window")?,
+            vec![
+                ("MOZ_DBUS_REMOTE".into(), "0".into()),
+                ("MOZ_ENABLE_WAYLAND".into(), "1".into())
+            ]
+        );
+        assert_eq!(env("chromium.desktop")?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_handlers_round_trip() -> Result<()> {
+        // The default config is written when there is no config file yet
+        let default = serialize(&ConfigFile::default())?.to_string();
+        assert_eq!(ConfigFile::from_str(&default)?.get(None)?, default);
+
+        let mut config = ConfigFile {
+            handler_env: BTreeMap::from([(
+                "firefox.desktop".into(),
+                BTreeMap::from([("MOZ_ENABLE_WAYLAND".into(), "1".into())]),
+            )]),
+            ..Default::default()
+        };
+        config.handlers.set_handler(
+            "freetube %u",
+            false,
+            &[r"(https://)?(www\.)?youtu(be\.com|\.be)/*".into()],
+        )?;
+        config
+            .handlers
+            .set_handler("nvim %f", true, &[r"\.rs$".into()])?;
+
+        let loaded = ConfigFile::from_str(&config.to_document()?.to_string())?;
+
+        assert_eq!(loaded.handlers, config.handlers);
+        assert_eq!(loaded.handler_env, config.handler_env);
+
+        Ok(())
+    }
+
+    #[test]
+    fn preserve_formatting() -> Result<()> {
+        let mut config = ConfigFile::from_str(
+            r#"# My handlr config
+enable_selector = false # Not yet
+
+# Video players
+[[handlers]]
+exec = "mpv %u" # Plays almost anything
+regexes = ['youtu\.be/']
+
+[[handlers]]
+exec = "nvim %f"
+terminal = true
+regexes = ['\.rs$']
+
+[handler_env."firefox.desktop"]
+MOZ_ENABLE_WAYLAND = "1"
+"#,
+        )?;
+
+        // Nothing is changed if nothing is set
+        insta::assert_snapshot!(config.to_document()?.to_string());
+
+        config.set("enable_selector", "true")?;
+        config.set("selector", "fuzzel --dmenu")?;
+        config.handlers.add_handler(
+            "mpv %u",
+            None,
+            &[r"vimeo\.com/".into()],
+        )?;
+        config.handlers.remove_handler("nvim %f", &[])?;
+        insta::assert_snapshot!(config.to_document()?.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_and_set() -> Result<()> {
+        let mut config = ConfigFile::default();
+
+        assert_eq!(config.get(Some("selector"))?, config.selector);
+        assert_eq!(config.get(Some("handler_grace_period"))?, "0");
+        assert!(matches!(
+            config.get(Some("nonexistent")),
+            Err(Error::UnknownConfigKey(_))
+        ));
+
+        config.set("handler_grace_period", "500")?;
+        assert_eq!(config.handler_grace_period, 500);
+        config.set("term_exec_args", "--")?;
+        assert_eq!(config.term_exec_args, Some("--".into()));
+
+        // Values with the wrong type are rejected, leaving the config as it was
+        assert!(config.set("enable_selector", "yes").is_err());
+        assert!(!config.enable_selector);
+        assert_eq!(config.get(Some("handler_grace_period"))?, "500");
+
+        assert!(matches!(
+            config.set("nonexistent", "true"),
+            Err(Error::UnknownConfigKey(_))
+        ));
+
+        Ok(())
+    }
+}
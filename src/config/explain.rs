@@ -0,0 +1,240 @@
+use crate::{apps::select, common::MimeSource};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{Display, Formatter, Result};
+
+/// A trace of how the handler for a path/URL or mime is chosen
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    /// The path/URL or mime being explained
+    pub input: String,
+    /// What the path/URL was rewritten to by the config's rewrite rules, if anything
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewritten: Option<String>,
+    /// The mime used to look up handlers
+    pub mime: String,
+    /// How the mime was determined
+    pub mime_source: MimeSource,
+    /// Every regex handler tested against the path/URL, in order
+    pub regex_handlers: Vec<RegexTrace>,
+    /// Default apps for the mime from each mimeapps.list in the lookup chain that has any
+    pub default_apps: Vec<LayerTrace>,
+    /// Added associations for the mime from every mimeapps.list in the lookup chain
+    pub added_associations: Vec<String>,
+    /// Handlers whose association with the mime has been removed
+    pub removed_associations: Vec<String>,
+    /// Installed applications that support the mime
+    pub system_apps: Vec<String>,
+    /// Handlers that were passed over, and why
+    pub skipped: Vec<SkippedHandler>,
+    /// Whether the selector would be run to choose between multiple handlers
+    pub selector: bool,
+    /// The handler that would be used, if any
+    pub handler: Option<ChosenHandler>,
+}
+
+/// The steps taken while looking up a handler
+/// Every lookup records one, so explaining a lookup cannot drift from how it is actually done
+#[derive(Debug, Default)]
+pub struct Trace {
+    /// Whether the lookup is only being explained, so the selector should not actually be run
+    explaining: bool,
+    /// Every regex handler tested against the path/URL, in order
+    pub regex_handlers: Vec<RegexTrace>,
+    /// Handlers that were passed over, and why
+    pub skipped: Vec<SkippedHandler>,
+    /// Whether the selector was or would be run to choose between multiple handlers
+    pub selector: bool,
+    /// The handler that was chosen, if any
+    pub handler: Option<ChosenHandler>,
+}
+
+impl Trace {
+    /// Create a trace for a lookup that is only being explained
+    pub fn explaining() -> Self {
+        Self {
+            explaining: true,
+            ..Default::default()
+        }
+    }
+
+    /// Record that a handler was passed over
+    pub fn skip(&mut self, handler: impl Display, reason: impl Display) {
+        self.skipped.push(SkippedHandler {
+            handler: handler.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Record the chosen handler and where it was found
+    pub fn choose(&mut self, handler: impl Display, source: impl Display) {
+        self.handler = Some(ChosenHandler {
+            handler: handler.to_string(),
+            source: source.to_string(),
+        });
+    }
+
+    /// Run the given selector to choose between the given handlers, listed by name
+    /// When explaining, the selector is not run and the first handler is chosen instead
+    pub fn select<H>(
+        &mut self,
+        selector: &str,
+        mut handlers: Vec<(H, String)>,
+    ) -> crate::error::Result<H> {
+        self.selector = true;
+
+        if self.explaining {
+            Ok(handlers.remove(0).0)
+        } else {
+            select(selector, handlers)
+        }
+    }
+}
+
+/// A regex handler tested against a path/URL
+#[derive(Debug, Serialize)]
+pub struct RegexTrace {
+    pub handler: String,
+    /// The handler's priority, where handlers are tested from highest to lowest
+    pub priority: i64,
+    /// The handler's regexes that matched
+    pub matches: Vec<String>,
+    /// Why the handler does not apply despite matching, if it does not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered: Option<String>,
+}
+
+impl RegexTrace {
+    /// Whether the handler matched and passed its filters
+    pub fn is_match(&self) -> bool {
+        !self.matches.is_empty() && self.filtered.is_none()
+    }
+}
+
+/// The default apps for a mime in a single mimeapps.list
+#[derive(Debug, Serialize)]
+pub struct LayerTrace {
+    /// Path of the mimeapps.list
+    pub source: String,
+    /// Handlers set for exactly the mime
+    pub exact: Option<Vec<String>>,
+    /// Wildcard associations matching the mime
+    pub wildcards: Vec<WildcardTrace>,
+}
+
+/// A wildcard association matching a mime
+/// When there is no exact match, the longest wildcard is used
+#[derive(Debug, Serialize)]
+pub struct WildcardTrace {
+    pub mime: String,
+    pub length: usize,
+    pub handlers: Vec<String>,
+}
+
+/// A handler that was passed over
+#[derive(Debug, Serialize)]
+pub struct SkippedHandler {
+    pub handler: String,
+    pub reason: String,
+}
+
+/// The handler that would be used, along with where it was found
+#[derive(Debug, Serialize)]
+pub struct ChosenHandler {
+    pub handler: String,
+    pub source: String,
+}
+
+/// Helper function to write a list of handlers, or `none` if it is empty
+fn write_list(f: &mut Formatter<'_>, name: &str, list: &[String]) -> Result {
+    if list.is_empty() {
+        writeln!(f, "{}: none", name)
+    } else {
+        writeln!(f, "{}: {}", name, list.join(", "))
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(rewritten) = &self.rewritten {
+            writeln!(f, "Rewritten to: {}", rewritten)?;
+        }
+
+        writeln!(f, "Mime: {} (from {})", self.mime, self.mime_source)?;
+
+        if self.regex_handlers.is_empty() {
+            writeln!(f, "Regex handlers: none")?;
+        } else {
+            writeln!(f, "Regex handlers:")?;
+            for regex in &self.regex_handlers {
+                write!(f, "  {}", regex.handler)?;
+                if regex.priority != 0 {
+                    write!(f, " (priority {})", regex.priority)?;
+                }
+
+                if regex.matches.is_empty() {
+                    writeln!(f, ": no match")?;
+                } else {
+                    write!(
+                        f,
+                        ": matched {}",
+                        regex
+                            .matches
+                            .iter()
+                            .map(|m| format!("`{m}`"))
+                            .join(", ")
+                    )?;
+                    match &regex.filtered {
+                        Some(reason) => writeln!(f, ", but {}", reason)?,
+                        None => writeln!(f)?,
+                    }
+                }
+            }
+        }
+
+        if self.default_apps.is_empty() {
+            writeln!(f, "Default apps: none")?;
+        } else {
+            writeln!(f, "Default apps:")?;
+            for layer in &self.default_apps {
+                writeln!(f, "  {}", layer.source)?;
+                if let Some(exact) = &layer.exact {
+                    writeln!(f, "    {}: {}", self.mime, exact.join(", "))?;
+                }
+                for wildcard in &layer.wildcards {
+                    writeln!(
+                        f,
+                        "    {} (wildcard, length {}): {}",
+                        wildcard.mime,
+                        wildcard.length,
+                        wildcard.handlers.join(", ")
+                    )?;
+                }
+            }
+        }
+
+        write_list(f, "Added associations", &self.added_associations)?;
+        write_list(f, "Removed associations", &self.removed_associations)?;
+        write_list(f, "Installed applications", &self.system_apps)?;
+
+        if !self.skipped.is_empty() {
+            writeln!(f, "Skipped:")?;
+            for skipped in &self.skipped {
+                writeln!(f, "  {}: {}", skipped.handler, skipped.reason)?;
+            }
+        }
+
+        if self.selector {
+            writeln!(f, "Selector: would be run to choose a handler")?;
+        }
+
+        match &self.handler {
+            Some(handler) => write!(
+                f,
+                "Handler: {} (from {})",
+                handler.handler, handler.source
+            ),
+            None => write!(f, "Handler: none found"),
+        }
+    }
+}
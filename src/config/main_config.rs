@@ -3,6 +3,7 @@
 
 use itertools::Itertools;
 use mime::Mime;
+use once_cell::unsync::OnceCell;
 use serde::Serialize;
 use std::{
     collections::{BTreeMap, HashMap, VecDeque},
@@ -11,13 +12,19 @@ use std::{
     str::FromStr,
 };
 use tabled::Tabled;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     apps::{DesktopList, MimeApps, SystemApps},
     cli::SelectorArgs,
-    common::{render_table, DesktopHandler, Handleable, Handler, UserPath},
-    config::config_file::ConfigFile,
+    common::{
+        render_table, DesktopHandler, Handleable, Handler, MimeOrPath,
+        RegexApps, RegexHandler, RegexHandlerEntry, UserPath,
+    },
+    config::{
+        config_file::ConfigFile,
+        explain::{Explanation, LayerTrace, Trace, WildcardTrace},
+    },
     error::{Error, Result},
 };
 
@@ -27,8 +34,8 @@ use crate::{
 pub struct Config {
     /// User-configured associations
     mime_apps: MimeApps,
-    /// Available applications on the system
-    system_apps: SystemApps,
+    /// Available applications on the system, loaded on first use
+    system_apps: OnceCell<SystemApps>,
     /// Handlr-specific config file
     pub config: ConfigFile,
     /// Whether or not stdout is a terminal
@@ -43,16 +50,36 @@ impl Config {
         Ok(Self {
             // Ensure fields individually default rather than making the whole thing fail if one is missing
             mime_apps: MimeApps::read()?,
-            system_apps: SystemApps::populate()?,
+            system_apps: OnceCell::new(),
             config: config?,
             terminal_output,
         })
     }
 
+    /// Get the available applications on the system, loading them if needed
+    fn system_apps(&self) -> Result<&SystemApps> {
+        self.system_apps.get_or_try_init(|| {
+            if cfg!(test) {
+                Ok(SystemApps::default())
+            } else {
+                SystemApps::load()
+            }
+        })
+    }
+
     /// Get the handler associated with a given mime
-    #[mutants::skip] // Cannot test match guard because it relies on user interactivity
     pub fn get_handler(&self, mime: &Mime) -> Result<DesktopHandler> {
-        match self.mime_apps.get_handler_from_user(mime, &self.config) {
+        self.trace_handler(mime, &mut Trace::default())
+    }
+
+    /// Get the handler associated with a given mime, recording the steps taken in the given trace
+    #[mutants::skip] // Cannot test match guard because it relies on user interactivity
+    fn trace_handler(
+        &self,
+        mime: &Mime,
+        trace: &mut Trace,
+    ) -> Result<DesktopHandler> {
+        match self.mime_apps.get_handler_from_user(mime, &self.config, trace) {
             Err(e) if matches!(e, Error::Cancelled) => Err(e),
             h => h
                 .inspect(|_| {
@@ -60,7 +87,7 @@ impl Config {
                 })
                 .or_else(|_|{
                     info!("No match for `{}` in mimeapps.list Default Associations", mime);
-                    self.get_handler_from_added_associations(mime)}),
+                    self.get_handler_from_added_associations(mime, trace)}),
         }
     }
 
@@ -69,24 +96,29 @@ impl Config {
     fn get_handler_from_added_associations(
         &self,
         mime: &Mime,
+        trace: &mut Trace,
     ) -> Result<DesktopHandler> {
-        self.mime_apps
-            .added_associations
-            .get(mime)
-            .inspect(|_|
-                info!("Found matching entry for `{}` in mimeapps.list Added Associations", mime)
-            )
-            .map_or_else(
-                || {
-                    info!("No matching entries for `{}` in mimeapps.list Added Associations", mime);
-                    self.system_apps.get_handler(mime)
-                },
-                |h| h.front().cloned(),
-            )
-            .ok_or_else(|| {
-                info!("No matching installed handlers found for `{}`", mime);
-                Error::NotFound(mime.to_string())
-            })
+        let handler = match self.mime_apps.get_added_associations(mime) {
+            Some(added) => {
+                info!("Found matching entry for `{}` in mimeapps.list Added Associations", mime);
+                added.front().cloned().inspect(|handler| {
+                    trace.choose(handler, "added associations")
+                })
+            }
+            None => {
+                info!("No matching entries for `{}` in mimeapps.list Added Associations", mime);
+                self.system_apps()?.get_handler(
+                    mime,
+                    &self.mime_apps.get_removed_associations(mime),
+                    trace,
+                )
+            }
+        };
+
+        handler.ok_or_else(|| {
+            info!("No matching installed handlers found for `{}`", mime);
+            Error::NotFound(mime.to_string())
+        })
     }
 
     /// Given a mime and arguments, launch the associated handler with the arguments
@@ -96,23 +128,67 @@ impl Config {
             "Launching handler for `{}` with arguments: {:?}",
             mime, args
         );
-        self.get_handler(mime)?
-            .launch(self, args.into_iter().map(|a| a.to_string()).collect())?;
+        let mut handler = self.get_handler(mime)?;
+        let mut failed = Vec::new();
+
+        loop {
+            match handler.launch(self, args.clone()) {
+                Err(e)
+                    if e.is_handler_failure()
+                        && self.config.fallback_on_failure =>
+                {
+                    failed.push(handler);
+                    let Some(fallback) = self.fallback_handler(mime, &failed)
+                    else {
+                        return Err(e);
+                    };
+                    warn!("{}", e);
+                    info!("Falling back to `{}`", fallback);
+                    handler = fallback;
+                }
+                result => break result?,
+            }
+        }
+
         info!("Finished launching handler");
         Ok(())
     }
 
-    /// Get the handler associated with a given mime
-    pub fn show_handler<W: Write>(
+    /// Given a mime and arguments, print the command lines that would be run to launch the associated handler
+    pub fn show_launch_cmds<W: Write>(
         &self,
         writer: &mut W,
         mime: &Mime,
+        args: Vec<String>,
+    ) -> Result<()> {
+        info!(
+            "Showing commands to launch handler for `{}` with arguments: {:?}",
+            mime, args
+        );
+
+        write_cmds(writer, self.get_handler(mime)?.launch_cmds(self, args)?)?;
+
+        info!("Finished showing commands");
+        Ok(())
+    }
+
+    /// Show the handler associated with a given mime or path/URL
+    /// Regex handlers are only taken into account for paths/URLs
+    pub fn show_handler<W: Write>(
+        &self,
+        writer: &mut W,
+        target: &MimeOrPath,
         output_json: bool,
     ) -> Result<()> {
-        info!("Showing handler for `{}`", mime);
+        info!("Showing handler for `{}`", target);
         debug!("JSON output: {}", output_json);
 
-        let handler = self.get_handler(mime)?;
+        let handler = match target {
+            MimeOrPath::Mime(mime, _) => self.get_handler(mime)?.into(),
+            MimeOrPath::Path(path) => {
+                self.get_handler_from_path(&self.rewrite(path)?)?
+            }
+        };
 
         let output = if output_json {
             let entry = handler.get_entry()?;
@@ -133,6 +209,111 @@ impl Config {
         Ok(())
     }
 
+    /// Trace how the handler for a given path/URL or mime is chosen
+    /// Runs the same lookup as opening it, without running the selector,
+    /// along with every association that could have applied
+    fn explain(&self, target: &MimeOrPath) -> Result<Explanation> {
+        let input = target.to_string();
+        let rewritten = match target {
+            MimeOrPath::Mime(..) => None,
+            MimeOrPath::Path(path) => {
+                self.config.rewrites.apply(path)?.map(MimeOrPath::Path)
+            }
+        };
+        let target = rewritten.as_ref().unwrap_or(target);
+
+        let (mime, mime_source) = match target {
+            MimeOrPath::Mime(mime, source) => (mime.0.clone(), *source),
+            MimeOrPath::Path(path) => path.get_mime_with_source()?,
+        };
+        let to_strings = |handlers: &DesktopList| {
+            handlers.iter().map(|h| h.to_string()).collect_vec()
+        };
+
+        let mut trace = Trace::explaining();
+        let lookup = match target {
+            MimeOrPath::Mime(..) => {
+                self.trace_handler(&mime, &mut trace).map(Handler::from)
+            }
+            MimeOrPath::Path(path) => {
+                self.trace_handler_from_path(path, &mut trace)
+            }
+        };
+        match lookup {
+            Ok(_) | Err(Error::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let default_apps = self
+            .mime_apps
+            .sourced_associations()
+            .filter_map(|(source, layer)| {
+                let exact = layer.default_apps.get(&mime);
+                let wildcards = layer
+                    .wildcard_candidates(&mime)
+                    .filter(|(wildcard, _)| **wildcard != mime)
+                    .map(|(wildcard, handlers)| WildcardTrace {
+                        mime: wildcard.to_string(),
+                        length: wildcard.as_ref().len(),
+                        handlers: to_strings(handlers),
+                    })
+                    .collect_vec();
+
+                (exact.is_some() || !wildcards.is_empty()).then(|| LayerTrace {
+                    source,
+                    exact: exact.map(to_strings),
+                    wildcards,
+                })
+            })
+            .collect_vec();
+
+        let added_associations = self
+            .mime_apps
+            .get_added_associations(&mime)
+            .unwrap_or_default();
+        let removed_associations =
+            self.mime_apps.get_removed_associations(&mime);
+        let system_apps =
+            self.system_apps()?.get_handlers(&mime).unwrap_or_default();
+
+        Ok(Explanation {
+            input,
+            rewritten: rewritten.map(|target| target.to_string()),
+            mime: mime.to_string(),
+            mime_source,
+            regex_handlers: trace.regex_handlers,
+            default_apps,
+            added_associations: to_strings(&added_associations),
+            removed_associations: to_strings(&removed_associations),
+            system_apps: to_strings(&system_apps),
+            skipped: trace.skipped,
+            selector: trace.selector,
+            handler: trace.handler,
+        })
+    }
+
+    /// Show how the handler for a given path/URL or mime is chosen
+    pub fn show_explanation<W: Write>(
+        &self,
+        writer: &mut W,
+        target: &MimeOrPath,
+        output_json: bool,
+    ) -> Result<()> {
+        info!("Explaining handler for `{}`", target);
+        debug!("JSON output: {}", output_json);
+
+        let explanation = self.explain(target)?;
+
+        if output_json {
+            writeln!(writer, "{}", serde_json::to_string(&explanation)?)?;
+        } else {
+            writeln!(writer, "{}", explanation)?;
+        }
+
+        info!("Finished explaining handler");
+        Ok(())
+    }
+
     /// Set a default application association, overwriting any existing association for the same mimetype
     /// and writes it to mimeapps.list
     pub fn set_handler(
@@ -187,11 +368,13 @@ impl Config {
 
         info!("Started opening paths: {}", format_paths(paths));
 
+        let paths = &self.config.rewrites.apply_all(paths)?;
+
         for (handler, paths) in
             self.assign_files_to_handlers(paths)?.into_iter()
         {
             debug!("Opening {} using `{}`", format_paths(&paths), handler);
-            handler.open(self, paths)?;
+            self.open_with_fallback(handler, paths)?;
         }
 
         info!("Finished opening paths");
@@ -199,6 +382,129 @@ impl Config {
         Ok(())
     }
 
+    /// Print the command lines that would be run to open the given paths with their respective handlers
+    pub fn show_open_cmds<W: Write>(
+        &self,
+        writer: &mut W,
+        paths: &[UserPath],
+    ) -> Result<()> {
+        info!("Showing commands to open paths");
+
+        let paths = &self.config.rewrites.apply_all(paths)?;
+        let inputs = paths.iter().map(|path| path.to_string()).collect_vec();
+
+        // Keep the output in the same order as the given paths
+        for (handler, paths) in self
+            .assign_files_to_handlers(paths)?
+            .into_iter()
+            .sorted_by_key(|(_, paths)| {
+                inputs.iter().position(|input| *input == paths[0])
+            })
+        {
+            debug!("Commands for `{}`", handler);
+            write_cmds(writer, handler.open_cmds(self, paths)?)?;
+        }
+
+        info!("Finished showing commands");
+        Ok(())
+    }
+
+    /// Open the given paths with the given handler
+    /// If it fails and falling back is enabled, each path is opened with the next handler for its mime instead
+    #[mutants::skip] // Cannot test directly, runs external commands
+    fn open_with_fallback(
+        &self,
+        handler: Handler,
+        paths: Vec<String>,
+    ) -> Result<()> {
+        let mut failed = Vec::new();
+        let mut queue = vec![(handler, paths)];
+
+        while let Some((handler, paths)) = queue.pop() {
+            match handler.open(self, paths.clone()) {
+                Err(e)
+                    if e.is_handler_failure()
+                        && self.config.fallback_on_failure =>
+                {
+                    // Regex handlers are not part of any list of handlers to fall back on
+                    let Handler::DesktopHandler(handler) = handler else {
+                        return Err(e);
+                    };
+                    failed.push(handler);
+
+                    let Some(fallbacks) = paths
+                        .into_iter()
+                        .map(|path| {
+                            let mime = UserPath::from_str(&path)?.get_mime()?;
+                            Ok(self
+                                .fallback_handler(&mime, &failed)
+                                .map(|fallback| (fallback, path)))
+                        })
+                        .collect::<Result<Option<Vec<_>>>>()?
+                    else {
+                        return Err(e);
+                    };
+
+                    warn!("{}", e);
+                    for (fallback, paths) in
+                        fallbacks.into_iter().into_group_map()
+                    {
+                        info!("Falling back to `{}`", fallback);
+                        queue.push((fallback.into(), paths));
+                    }
+                }
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the next handler to try for a given mime, skipping any that already failed
+    /// Handlers are tried in the same order they are looked up in: mimeapps.list's default apps,
+    /// then its added associations, then the installed applications
+    fn fallback_handler(
+        &self,
+        mime: &Mime,
+        failed: &[DesktopHandler],
+    ) -> Option<DesktopHandler> {
+        let default_apps = self
+            .mime_apps
+            .get_default_handlers(mime)
+            .cloned()
+            .unwrap_or_default();
+        let added_associations = self
+            .mime_apps
+            .get_added_associations(mime)
+            .unwrap_or_default();
+        let removed_associations =
+            self.mime_apps.get_removed_associations(mime);
+        let system_apps = self
+            .system_apps()
+            .ok()
+            .and_then(|system_apps| system_apps.get_handlers(mime))
+            .unwrap_or_default();
+
+        default_apps
+            .iter()
+            .chain(added_associations.iter())
+            .chain(
+                system_apps
+                    .iter()
+                    .filter(|handler| !removed_associations.contains(handler)),
+            )
+            .filter(|handler| !failed.contains(handler))
+            .find(|handler| {
+                let is_available =
+                    handler.get_entry().is_ok_and(|entry| entry.is_available());
+                if !is_available {
+                    debug!("Skipping unavailable handler `{}`", handler);
+                }
+                is_available
+            })
+            .cloned()
+    }
+
     /// Helper function to assign files to their respective handlers
     fn assign_files_to_handlers(
         &self,
@@ -216,15 +522,108 @@ impl Config {
         Ok(handlers)
     }
 
+    /// Apply the rewrite rules to a given path/URL, keeping it as-is if none apply
+    fn rewrite(&self, path: &UserPath) -> Result<UserPath> {
+        Ok(self
+            .config
+            .rewrites
+            .apply(path)?
+            .unwrap_or_else(|| path.clone()))
+    }
+
     /// Get the handler associated with a given path
     fn get_handler_from_path(&self, path: &UserPath) -> Result<Handler> {
-        Ok(if let Ok(handler) = self.config.get_regex_handler(path) {
-            info!("Using regex handler for `{}`", path);
-            handler.into()
-        } else {
+        self.trace_handler_from_path(path, &mut Trace::default())
+    }
+
+    /// Get the handler associated with a given path, recording the steps taken in the given trace
+    /// If enabled, the selector is run if multiple regex handlers match
+    /// or one matches and the path's mime has a handler as well
+    #[mutants::skip] // Cannot entirely test, namely cannot test selector
+    fn trace_handler_from_path(
+        &self,
+        path: &UserPath,
+        trace: &mut Trace,
+    ) -> Result<Handler> {
+        let regex_handlers = self.config.get_regex_handlers(path, trace);
+
+        let Some(handler) = regex_handlers.first().cloned() else {
             info!("No matching regex handlers found for `{}`", path);
-            self.get_handler(&path.get_mime()?)?.into()
-        })
+            return Ok(self.trace_handler(&path.get_mime()?, trace)?.into());
+        };
+        trace.choose(&handler, "regex handlers");
+
+        if self.select_regex_handlers() {
+            let candidates = self.handler_candidates(path, regex_handlers);
+            debug!(
+                "Number of handlers to choose between for `{}`: {}",
+                path,
+                candidates.len()
+            );
+
+            if candidates.len() > 1 {
+                info!("Running selector: {}", &self.config.selector);
+                return trace.select(&self.config.selector, candidates);
+            }
+        }
+
+        info!("Using regex handler for `{}`", path);
+        Ok(handler.into())
+    }
+
+    /// Whether to run the selector to choose between the regex handlers matching a path/URL
+    fn select_regex_handlers(&self) -> bool {
+        self.config.enable_selector && self.config.select_regex_handlers
+    }
+
+    /// Get every handler to choose between for a given path along with their names,
+    /// given the regex handlers matching it
+    /// The regex handlers come first, followed by the default handlers for the path's mime
+    fn handler_candidates(
+        &self,
+        path: &UserPath,
+        regex_handlers: Vec<RegexHandler>,
+    ) -> Vec<(Handler, String)> {
+        let mime_handlers = path
+            .get_mime()
+            .map(|mime| self.mime_handler_candidates(&mime))
+            .unwrap_or_default();
+
+        regex_handlers
+            .into_iter()
+            .map(|handler| {
+                let name = handler.to_string();
+                (handler.into(), name)
+            })
+            .chain(
+                mime_handlers
+                    .into_iter()
+                    .map(|(handler, name)| (handler.into(), name)),
+            )
+            .collect_vec()
+    }
+
+    /// Get the default handlers for a given mime along with their names
+    /// If none are set in mimeapps.list, the handler it would otherwise fall back on is used
+    fn mime_handler_candidates(
+        &self,
+        mime: &Mime,
+    ) -> Vec<(DesktopHandler, String)> {
+        self.mime_apps
+            .get_handlers_from_user(mime)
+            .unwrap_or_else(|_| {
+                self.get_handler_from_added_associations(
+                    mime,
+                    &mut Trace::default(),
+                )
+                .map(|handler| {
+                    let name = handler
+                        .get_entry()
+                        .map_or_else(|_| handler.to_string(), |e| e.name);
+                    vec![(handler, name)]
+                })
+                .unwrap_or_default()
+            })
     }
 
     /// Get the command for the x-scheme-handler/terminal handler if one is set.
@@ -236,7 +635,7 @@ impl Config {
             .ok()
             .and_then(|h| h.get_entry().ok())
             // Otherwise, get a terminal emulator program
-            .or_else(|| self.system_apps.terminal_emulator())
+            .or_else(|| self.system_apps().ok()?.terminal_emulator())
             .map(|e| {
                 let mut exec = e.exec.to_owned();
 
@@ -260,13 +659,14 @@ impl Config {
         info!("Printing associations");
         debug!("JSON output: {}", output_json);
 
-        let mimeapps_table = MimeAppsTable::new(
-            &self.mime_apps,
-            &self.system_apps,
-            self.terminal_output,
-        );
-
         if detailed {
+            let mimeapps_table = MimeAppsTable::new(
+                &self.mime_apps,
+                self.system_apps()?,
+                &self.config.handlers,
+                self.terminal_output,
+            );
+
             if output_json {
                 writeln!(writer, "{}", serde_json::to_string(&mimeapps_table)?)?
             } else {
@@ -279,7 +679,7 @@ impl Config {
                         self.terminal_output
                     )
                 )?;
-                if !self.mime_apps.added_associations.is_empty() {
+                if !mimeapps_table.added_associations.is_empty() {
                     writeln!(writer, "Added associations")?;
                     writeln!(
                         writer,
@@ -290,6 +690,17 @@ impl Config {
                         )
                     )?;
                 }
+                if !mimeapps_table.removed_associations.is_empty() {
+                    writeln!(writer, "Removed associations")?;
+                    writeln!(
+                        writer,
+                        "{}",
+                        render_table(
+                            &mimeapps_table.removed_associations,
+                            self.terminal_output
+                        )
+                    )?;
+                }
                 writeln!(writer, "System Apps")?;
                 writeln!(
                     writer,
@@ -298,29 +709,139 @@ impl Config {
                         &mimeapps_table.system_apps,
                         self.terminal_output
                     )
-                )?
+                )?;
+                if !mimeapps_table.regex_handlers.is_empty() {
+                    writeln!(writer, "Regex Handlers")?;
+                    writeln!(
+                        writer,
+                        "{}",
+                        render_table(
+                            &mimeapps_table.regex_handlers,
+                            self.terminal_output
+                        )
+                    )?;
+                }
             }
-        } else if output_json {
-            writeln!(
-                writer,
-                "{}",
-                serde_json::to_string(&mimeapps_table.default_apps)?
-            )?
         } else {
-            writeln!(
-                writer,
-                "{}",
-                render_table(
-                    &mimeapps_table.default_apps,
-                    self.terminal_output
-                )
-            )?
+            let default_apps = MimeAppsEntry::from_map(
+                &self.mime_apps.default_apps,
+                self.terminal_output,
+            );
+
+            if output_json {
+                writeln!(writer, "{}", serde_json::to_string(&default_apps)?)?
+            } else {
+                writeln!(
+                    writer,
+                    "{}",
+                    render_table(&default_apps, self.terminal_output)
+                )?
+            }
         }
 
         info!("Finished printing associations");
         Ok(())
     }
 
+    /// Set the regexes of a regex handler, overwriting any existing ones,
+    /// and write it to handlr.toml
+    /// The handler's priority is only changed if one is given
+    pub fn set_regex_handler(
+        &mut self,
+        exec: &str,
+        terminal: bool,
+        regexes: &[String],
+        priority: Option<i64>,
+    ) -> Result<()> {
+        info!(
+            "Setting regexes for regex handler `{}`: {:?}",
+            exec, regexes
+        );
+
+        self.config.handlers.set_handler(exec, terminal, regexes)?;
+        if let Some(priority) = priority {
+            self.config.handlers.set_priority(exec, priority);
+        }
+        self.config.save()?;
+
+        info!("Finished setting regex handler");
+        Ok(())
+    }
+
+    /// Add regexes to a regex handler, creating it if necessary,
+    /// and write it to handlr.toml
+    /// The handler's terminal flag and priority are only changed if they are given
+    pub fn add_regex_handler(
+        &mut self,
+        exec: &str,
+        terminal: Option<bool>,
+        regexes: &[String],
+        priority: Option<i64>,
+    ) -> Result<()> {
+        info!("Adding regexes to regex handler `{}`: {:?}", exec, regexes);
+
+        self.config.handlers.add_handler(exec, terminal, regexes)?;
+        if let Some(priority) = priority {
+            self.config.handlers.set_priority(exec, priority);
+        }
+        self.config.save()?;
+
+        info!("Finished adding regex handler");
+        Ok(())
+    }
+
+    /// Remove regexes from a regex handler, or the whole handler if none are given,
+    /// and write it to handlr.toml
+    pub fn remove_regex_handler(
+        &mut self,
+        exec: &str,
+        regexes: &[String],
+    ) -> Result<()> {
+        info!(
+            "Removing regexes from regex handler `{}`: {:?}",
+            exec, regexes
+        );
+
+        if self
+            .config
+            .handlers
+            .remove_handler(exec, regexes)?
+            .is_some()
+        {
+            self.config.save()?
+        } else {
+            return Err(Error::NotFound(exec.to_owned()));
+        }
+
+        info!("Finished removing regex handler");
+        Ok(())
+    }
+
+    /// Show the value of a key in handlr.toml, or the whole config if no key is given
+    pub fn show_config<W: Write>(
+        &self,
+        writer: &mut W,
+        key: Option<&str>,
+    ) -> Result<()> {
+        info!("Showing config value for `{}`", key.unwrap_or("*"));
+
+        writeln!(writer, "{}", self.config.get(key)?.trim_end())?;
+
+        info!("Finished showing config value");
+        Ok(())
+    }
+
+    /// Set the value of a key in handlr.toml and write it
+    pub fn set_config(&mut self, key: &str, value: &str) -> Result<()> {
+        info!("Setting config value for `{}`: {}", key, value);
+
+        self.config.set(key, value)?;
+        self.config.save()?;
+
+        info!("Finished setting config value");
+        Ok(())
+    }
+
     /// Entirely remove a given mime's default application association
     pub fn unset_handler(&mut self, mime: &Mime) -> Result<()> {
         info!("Unsetting handler for `{}`", mime);
@@ -352,6 +873,57 @@ impl Config {
         Ok(())
     }
 
+    /// Rebuild the cache of installed applications
+    #[mutants::skip] // Cannot test directly, depends on system state
+    pub fn rebuild_cache(&mut self) -> Result<()> {
+        info!("Rebuilding cache of installed applications");
+        self.system_apps = OnceCell::from(SystemApps::rebuild_cache()?);
+        Ok(())
+    }
+
+    /// Add a handler to a given mime's removed associations
+    /// and writes it to mimeapps.list
+    pub fn add_removed_association(
+        &mut self,
+        mime: &Mime,
+        handler: &DesktopHandler,
+    ) -> Result<()> {
+        info!(
+            "Adding `{}` to removed associations for `{}`",
+            handler, mime
+        );
+
+        self.mime_apps.add_removed_association(mime, handler);
+        self.mime_apps.save()?;
+
+        info!("Finished adding removed association");
+        Ok(())
+    }
+
+    /// Remove a handler from a given mime's removed associations
+    /// and writes it to mimeapps.list
+    pub fn remove_removed_association(
+        &mut self,
+        mime: &Mime,
+        handler: &DesktopHandler,
+    ) -> Result<()> {
+        info!(
+            "Removing `{}` from removed associations for `{}`",
+            handler, mime
+        );
+
+        if self
+            .mime_apps
+            .remove_removed_association(mime, handler)
+            .is_some()
+        {
+            self.mime_apps.save()?
+        }
+
+        info!("Finished removing removed association");
+        Ok(())
+    }
+
     /// Override the set selector
     /// Currently assumes the config file will never be saved to other than to create an existing one
     pub fn override_selector(&mut self, selector_args: SelectorArgs) {
@@ -393,14 +965,78 @@ impl MimeAppsEntry {
     fn display_handlers(&self) -> String {
         self.handlers.join(&self.separator)
     }
+
+    /// Create a sorted list of `MimeAppsEntry`s from a map of associations
+    fn from_map(
+        map: &BTreeMap<Mime, DesktopList>,
+        terminal_output: bool,
+    ) -> Vec<Self> {
+        // If output is a terminal, optimize for readability
+        // Otherwise, if piped, optimize for parseability
+        let separator = if terminal_output { ",\n" } else { ", " };
+
+        let mut rows = map
+            .iter()
+            .map(|(mime, handlers)| Self::new(mime, handlers, separator))
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+        rows
+    }
+}
+
+/// Write the given command lines, one per line, quoted like in a shell
+fn write_cmds<W: Write>(writer: &mut W, cmds: Vec<Vec<String>>) -> Result<()> {
+    for cmd in cmds {
+        writeln!(
+            writer,
+            "{}",
+            shlex::try_join(cmd.iter().map(String::as_str))
+                .map_err(|_| Error::UnquotableCmd(cmd.join(" ")))?
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Internal helper struct for turning MimeApps into tabular data
+/// along with the mimeapps.list each association came from
+#[derive(Tabled, Serialize)]
+struct SourcedMimeAppsEntry {
+    #[tabled(inline)]
+    #[serde(flatten)]
+    entry: MimeAppsEntry,
+    source: String,
+}
+
+impl SourcedMimeAppsEntry {
+    /// Create a list of `SourcedMimeAppsEntry`s from every mimeapps.list in the lookup chain
+    fn from_mime_apps(
+        mimeapps: &MimeApps,
+        associations: fn(&MimeApps) -> &BTreeMap<Mime, DesktopList>,
+        terminal_output: bool,
+    ) -> Vec<Self> {
+        mimeapps
+            .sourced_associations()
+            .flat_map(|(source, layer)| {
+                MimeAppsEntry::from_map(associations(layer), terminal_output)
+                    .into_iter()
+                    .map(move |entry| Self {
+                        entry,
+                        source: source.clone(),
+                    })
+            })
+            .collect()
+    }
 }
 
 /// Internal helper struct for turning MimeApps into tabular data
 #[derive(Serialize)]
 struct MimeAppsTable {
-    added_associations: Vec<MimeAppsEntry>,
-    default_apps: Vec<MimeAppsEntry>,
+    added_associations: Vec<SourcedMimeAppsEntry>,
+    default_apps: Vec<SourcedMimeAppsEntry>,
+    removed_associations: Vec<SourcedMimeAppsEntry>,
     system_apps: Vec<MimeAppsEntry>,
+    regex_handlers: Vec<RegexHandlerEntry>,
 }
 
 impl MimeAppsTable {
@@ -408,27 +1044,33 @@ impl MimeAppsTable {
     fn new(
         mimeapps: &MimeApps,
         system_apps: &SystemApps,
+        regex_apps: &RegexApps,
         terminal_output: bool,
     ) -> Self {
-        // If output is a terminal, optimize for readability
-        // Otherwise, if piped, optimize for parseability
-        let separator = if terminal_output { ",\n" } else { ", " };
-
-        let to_entries =
-            |map: &BTreeMap<Mime, DesktopList>| -> Vec<MimeAppsEntry> {
-                let mut rows = map
-                    .iter()
-                    .map(|(mime, handlers)| {
-                        MimeAppsEntry::new(mime, handlers, separator)
-                    })
-                    .collect::<Vec<_>>();
-                rows.sort_unstable();
-                rows
-            };
         Self {
-            added_associations: to_entries(&mimeapps.added_associations),
-            default_apps: to_entries(&mimeapps.default_apps),
-            system_apps: to_entries(&system_apps.associations),
+            added_associations: SourcedMimeAppsEntry::from_mime_apps(
+                mimeapps,
+                |layer| &layer.added_associations,
+                terminal_output,
+            ),
+            default_apps: SourcedMimeAppsEntry::from_mime_apps(
+                mimeapps,
+                |layer| &layer.default_apps,
+                terminal_output,
+            ),
+            removed_associations: SourcedMimeAppsEntry::from_mime_apps(
+                mimeapps,
+                |layer| &layer.removed_associations,
+                terminal_output,
+            ),
+            system_apps: MimeAppsEntry::from_map(
+                &system_apps.associations,
+                terminal_output,
+            ),
+            regex_handlers: RegexHandlerEntry::from_apps(
+                regex_apps,
+                terminal_output,
+            ),
         }
     }
 }
@@ -505,6 +1147,250 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn fallback_handlers() -> Result<()> {
+        let mut config = Config::default();
+        let mime = Mime::from_str("text/plain")?;
+        let handler = |name: &str| {
+            DesktopHandler::assume_valid(format!("tests/assets/{name}").into())
+        };
+        let list = |names: &[&str]| {
+            DesktopList::from_str(
+                &names
+                    .iter()
+                    .map(|name| format!("tests/assets/{name}"))
+                    .join(";"),
+            )
+        };
+
+        config.mime_apps.default_apps.insert(
+            mime.clone(),
+            list(&["cmus.desktop", "empty_exec.desktop"])?,
+        );
+        config
+            .mime_apps
+            .added_associations
+            .insert(mime.clone(), list(&["Helix.desktop", "cmus.desktop"])?);
+        config
+            .mime_apps
+            .removed_associations
+            .insert(mime.clone(), list(&["org.wezfurlong.wezterm.desktop"])?);
+        config.system_apps.get_or_init(SystemApps::default);
+        config
+            .system_apps
+            .get_mut()
+            .expect("System apps should be initialized")
+            .associations
+            .insert(
+                mime.clone(),
+                list(&[
+                    "org.wezfurlong.wezterm.desktop",
+                    "hidden.desktop",
+                    "field_codes.desktop",
+                ])?,
+            );
+
+        let mut failed = Vec::new();
+        let mut attempts = Vec::new();
+        while let Some(fallback) = config.fallback_handler(&mime, &failed) {
+            attempts.push(fallback.clone());
+            failed.push(fallback);
+        }
+
+        // Invalid, unavailable and removed handlers are skipped,
+        // as are handlers that already failed
+        assert_eq!(
+            attempts,
+            vec![
+                handler("cmus.desktop"),
+                handler("Helix.desktop"),
+                handler("field_codes.desktop")
+            ]
+        );
+        assert_eq!(
+            config.fallback_handler(&Mime::from_str("text/html")?, &[]),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn explain_handlers() -> Result<()> {
+        let mut config = Config::default();
+        let list = |names: &[&str]| {
+            DesktopList::from_str(
+                &names
+                    .iter()
+                    .map(|name| format!("tests/assets/{name}"))
+                    .join(";"),
+            )
+        };
+
+        config.config.enable_selector = true;
+        config.config.handlers = serde_json::from_value(serde_json::json!([
+            {
+                "exec": "freetube %u",
+                "regexes": [r"youtu(be\.com|\.be)/"],
+                "schemes": ["http"]
+            },
+            { "exec": "mpv %u", "regexes": [r"\.mkv$", r"youtu\.be/"] }
+        ]))?;
+        config.mime_apps.default_apps.insert(
+            Mime::from_str("text/*")?,
+            list(&[
+                "empty_exec.desktop",
+                "Helix.desktop",
+                "field_codes.desktop",
+            ])?,
+        );
+        config
+            .mime_apps
+            .default_apps
+            .insert(Mime::from_str("t*/*")?, list(&["cmus.desktop"])?);
+        config.mime_apps.added_associations.insert(
+            Mime::from_str("x-scheme-handler/https")?,
+            list(&["actions.desktop"])?,
+        );
+        config
+            .mime_apps
+            .removed_associations
+            .insert(Mime::from_str("audio/mpeg")?, list(&["cmus.desktop"])?);
+        config.system_apps.get_or_init(SystemApps::default);
+        config
+            .system_apps
+            .get_mut()
+            .expect("System apps should be initialized")
+            .associations
+            .insert(
+                Mime::from_str("audio/mpeg")?,
+                list(&[
+                    "hidden.desktop",
+                    "cmus.desktop",
+                    "field_codes.desktop",
+                ])?,
+            );
+
+        let explain = |target: &str, output_json: bool| -> Result<String> {
+            let mut buffer = Vec::new();
+            config.show_explanation(
+                &mut buffer,
+                &MimeOrPath::from_str(target)?,
+                output_json,
+            )?;
+            Ok(String::from_utf8(buffer)?)
+        };
+
+        // Wildcards, invalid entries and the selector
+        insta::assert_snapshot!(explain("tests/assets/p.html", false)?);
+        // Regex handlers, including ones that are filtered out
+        insta::assert_snapshot!(explain(
+            "https://youtu.be/dQw4w9WgXcQ",
+            false
+        )?);
+        // Added associations
+        insta::assert_snapshot!(explain("https://duckduckgo.com", false)?);
+        // Installed applications, removed associations and unavailable entries
+        insta::assert_snapshot!(explain(".mp3", false)?);
+        insta::assert_snapshot!(explain(".mp3", true)?);
+
+        // The explanation comes from the same lookup that is used to open things
+        config.config.enable_selector = false;
+        for target in [
+            "tests/assets/p.html",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://duckduckgo.com",
+            ".mp3",
+        ] {
+            let target = MimeOrPath::from_str(target)?;
+            let handler = match &target {
+                MimeOrPath::Mime(mime, _) => config.get_handler(mime)?.into(),
+                MimeOrPath::Path(path) => config.get_handler_from_path(path)?,
+            };
+            assert_eq!(
+                config.explain(&target)?.handler.map(|h| h.handler),
+                Some(handler.to_string())
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn regex_handler_priority() -> Result<()> {
+        let mut config = Config::default();
+
+        config.config.handlers = serde_json::from_value(serde_json::json!([
+            { "exec": "firefox %u", "regexes": [r"\.html$"] },
+            { "exec": "curl %u", "regexes": [r"^https://"], "priority": -1 },
+            { "exec": "w3m %u", "regexes": [r"\.html$"], "priority": 5 },
+        ]))?;
+        config.add_handler(
+            &Mime::from_str("text/html")?,
+            &DesktopHandler::assume_valid("Helix.desktop".into()),
+        )?;
+        config.add_handler(
+            &Mime::from_str("text/html")?,
+            &DesktopHandler::assume_valid("nvim.desktop".into()),
+        )?;
+
+        let path = UserPath::from_str("tests/assets/p.html")?;
+        let url = UserPath::from_str("https://example.com")?;
+
+        // The highest priority handler is used when the selector is not enabled for regex handlers
+        assert_eq!(
+            config.get_handler_from_path(&path)?.to_string(),
+            "\"w3m %u\" (Regex Handler)"
+        );
+
+        let candidates = |path: &UserPath| {
+            config
+                .handler_candidates(
+                    path,
+                    config
+                        .config
+                        .get_regex_handlers(path, &mut Trace::default()),
+                )
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect_vec()
+        };
+        assert_eq!(
+            candidates(&path),
+            [
+                "\"w3m %u\" (Regex Handler)",
+                "\"firefox %u\" (Regex Handler)",
+                "Helix.desktop",
+                "nvim.desktop"
+            ]
+        );
+        assert_eq!(candidates(&url), ["\"curl %u\" (Regex Handler)"]);
+
+        config.config.enable_selector = true;
+        config.config.select_regex_handlers = true;
+
+        // Only one handler to choose from, so there is no need for the selector
+        assert_eq!(
+            config.get_handler_from_path(&url)?.to_string(),
+            "\"curl %u\" (Regex Handler)"
+        );
+
+        let explain = |target: &str| -> Result<String> {
+            let mut buffer = Vec::new();
+            config.show_explanation(
+                &mut buffer,
+                &MimeOrPath::from_str(target)?,
+                false,
+            )?;
+            Ok(String::from_utf8(buffer)?)
+        };
+
+        insta::assert_snapshot!(explain("tests/assets/p.html")?);
+        insta::assert_snapshot!(explain("https://example.com")?);
+
+        Ok(())
+    }
+
     // Helper command to test the tables of handlers
     // Renders a table with a bunch of arbitrary handlers to a writer
     // TODO: test printing with non-empty system apps too
@@ -564,6 +1450,24 @@ mod tests {
                 "org.wezfurlong.wezterm.desktop".into(),
             ));
 
+        // Add arbitrary removed association
+        config
+            .mime_apps
+            .removed_associations
+            .entry(mime::TEXT_PLAIN)
+            .or_default()
+            .push_back(DesktopHandler::assume_valid("vim.desktop".into()));
+
+        // Add arbitrary regex handler
+        config.config.handlers.set_handler(
+            "freetube %u",
+            false,
+            &[
+                r"(https://)?(www\.)?youtu(be\.com|\.be)/*".into(),
+                r"(https://)?invidious\.example/*".into(),
+            ],
+        )?;
+
         // Set terminal output
         config.terminal_output = terminal_output;
 
@@ -638,8 +1542,11 @@ mod tests {
     crate::logs_snapshot_test!(terminal_command_fallback, {
         let mut config = Config::default();
 
+        config.system_apps.get_or_init(SystemApps::default);
         config
             .system_apps
+            .get_mut()
+            .expect("System apps should be initialized")
             .add_unassociated(DesktopHandler::from_str(
                 "tests/assets/org.wezfurlong.wezterm.desktop",
             )?);
@@ -671,7 +1578,130 @@ mod tests {
             )?,
         )?;
 
-        config.show_handler(writer, &mime::TEXT_PLAIN, output_json)?;
+        config.show_handler(
+            writer,
+            &MimeOrPath::from_str("text/plain")?,
+            output_json,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run() -> Result<()> {
+        let mut config = Config::default();
+
+        config.config.handlers = serde_json::from_value(serde_json::json!([
+            { "exec": "mpv %u", "regexes": [r"youtu\.be/"] }
+        ]))?;
+        config.add_handler(
+            &mime::TEXT_PLAIN,
+            &DesktopHandler::from_str("tests/assets/Helix.desktop")?,
+        )?;
+        config.add_handler(
+            &Mime::from_str("audio/mpeg")?,
+            &DesktopHandler::from_str("tests/assets/cmus.desktop")?,
+        )?;
+        config.add_handler(
+            &Mime::from_str("x-scheme-handler/terminal")?,
+            &DesktopHandler::from_str(
+                "tests/assets/org.wezfurlong.wezterm.desktop",
+            )?,
+        )?;
+
+        let mut buffer = Vec::new();
+        config.show_open_cmds(
+            &mut buffer,
+            &[
+                UserPath::from_str("b c.mp3")?,
+                UserPath::from_str("tests/assets/rust.vim")?,
+                UserPath::from_str("a.mp3")?,
+                UserPath::from_str("https://youtu.be/dQw4w9WgXcQ")?,
+                UserPath::from_str("tests/assets/empty.txt")?,
+            ],
+        )?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        let mut buffer = Vec::new();
+        config.show_launch_cmds(
+            &mut buffer,
+            &mime::TEXT_PLAIN,
+            vec!["--help".into(), "a b".into()],
+        )?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rewritten_paths() -> Result<()> {
+        let mut config = Config::default();
+        config.config.handlers = serde_json::from_value(serde_json::json!([
+            { "exec": "mpv %u", "regexes": [r"^https://youtu\.be/"] }
+        ]))?;
+        config.config.rewrites = serde_json::from_value(serde_json::json!([
+            {
+                "regex": r"^https://(www\.)?youtube\.com/watch\?v=([\w-]+)",
+                "replacement": "https://youtu.be/$2"
+            }
+        ]))?;
+
+        // Rewritten paths are used to choose the handler, and are what it opens
+        let mut buffer = Vec::new();
+        config.show_open_cmds(
+            &mut buffer,
+            &[UserPath::from_str(
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            )?],
+        )?;
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            "mpv https://youtu.be/dQw4w9WgXcQ\n"
+        );
+
+        // They are also used to show and explain the handler
+        let target = MimeOrPath::from_str(
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        )?;
+
+        let mut buffer = Vec::new();
+        config.show_handler(&mut buffer, &target, false)?;
+        assert_eq!(String::from_utf8(buffer)?, "\"mpv %u\" (Regex Handler)\n");
+
+        let mut buffer = Vec::new();
+        config.show_explanation(&mut buffer, &target, false)?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn show_regex_handler() -> Result<()> {
+        let mut config = Config::default();
+        config.config.handlers = serde_json::from_value(serde_json::json!([
+            { "exec": "freetube %u", "regexes": [r"youtu\.be/"] }
+        ]))?;
+
+        let mut buffer = Vec::new();
+        config.show_handler(
+            &mut buffer,
+            &MimeOrPath::from_str("https://youtu.be/dQw4w9WgXcQ")?,
+            true,
+        )?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        // Paths that do not match fall back to the mime's handler
+        config.add_handler(
+            &Mime::from_str("x-scheme-handler/https")?,
+            &DesktopHandler::assume_valid("firefox.desktop".into()),
+        )?;
+        let mut buffer = Vec::new();
+        config.show_handler(
+            &mut buffer,
+            &MimeOrPath::from_str("https://example.com")?,
+            false,
+        )?;
+        assert_eq!(String::from_utf8(buffer)?, "firefox.desktop\n");
 
         Ok(())
     }
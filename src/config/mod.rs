@@ -1,5 +1,7 @@
 mod config_file;
+mod explain;
 mod main_config;
 
 pub use config_file::ConfigFile;
+pub use explain::{RegexTrace, Trace};
 pub use main_config::Config;
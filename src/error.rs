@@ -8,7 +8,13 @@ pub enum Error {
     #[error(transparent)]
     Xdg(#[from] xdg::BaseDirectoriesError),
     #[error(transparent)]
-    Config(#[from] confy::ConfyError),
+    ConfigSyntax(#[from] toml_edit::TomlError),
+    #[error(transparent)]
+    ConfigRead(#[from] toml_edit::de::Error),
+    #[error(transparent)]
+    ConfigWrite(#[from] toml_edit::ser::Error),
+    #[error("Unknown config key '{0}'")]
+    UnknownConfigKey(String),
     #[error("No handlers found for '{0}'")]
     NotFound(String),
     #[error(
@@ -23,6 +29,10 @@ pub enum Error {
     BadEntry(std::path::PathBuf),
     #[error(transparent)]
     BadRegex(#[from] regex::Error),
+    #[error(transparent)]
+    BadGlob(#[from] globset::Error),
+    #[error("Regex handler '{0}' has no regexes or globs")]
+    NoPatterns(String),
     #[error("Error spawning selector process '{0}'")]
     Selector(String),
     #[error("Selection cancelled")]
@@ -32,13 +42,23 @@ pub enum Error {
     #[error("Bad path: {0}")]
     BadPath(String),
     #[error(transparent)]
+    DBus(#[from] zbus::Error),
+    #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
     #[error(transparent)]
     SerdeIniDe(#[from] serde_ini::de::Error),
-    #[error(transparent)]
-    SerdeIniSer(#[from] serde_ini::ser::Error),
     #[error("Could not split exec command '{0}' in desktop file '{1}' into shell words")]
     BadExec(String, String),
+    #[error("Cannot open '{0}' with '{1}', which only supports local files")]
+    NonLocalUrl(String, String),
+    #[error("No action '{0}' in desktop entry '{1}'")]
+    UnknownAction(String, String),
+    #[error("'{0}' exited unsuccessfully ({1}){}", stderr_suffix(.2))]
+    HandlerFailed(String, std::process::ExitStatus, String),
+    #[error("Could not run '{0}': {1}")]
+    Spawn(String, std::io::Error),
+    #[error("Could not quote command '{0}' as shell words")]
+    UnquotableCmd(String),
     #[error("Could not split command '{0}' into shell words")]
     BadCmd(String),
     #[error(transparent)]
@@ -59,6 +79,23 @@ impl Error {
             _ => error!("{}", self),
         }
     }
+
+    /// Whether the error means a handler could not be run, so another one may be tried instead
+    pub fn is_handler_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::HandlerFailed(..) | Self::Spawn(..) | Self::NonLocalUrl(..)
+        )
+    }
+}
+
+/// Format a failed handler's stderr to be appended to its error message
+fn stderr_suffix(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(":\n{}", stderr)
+    }
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
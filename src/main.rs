@@ -6,7 +6,7 @@ mod error;
 mod logging;
 mod testing;
 
-use cli::{Cli, Cmd};
+use cli::{CacheCmd, Cli, Cmd, ConfigCmd, RegexCmd, RemovedCmd};
 use common::mime_table;
 use config::Config;
 use error::Result;
@@ -48,31 +48,82 @@ fn run(cli: Cli) -> Result<()> {
         Cmd::Launch {
             mime,
             args,
+            dry_run,
             selector_args,
         } => {
             config.override_selector(selector_args);
-            config.launch_handler(&mime, args)
+            if dry_run {
+                config.show_launch_cmds(&mut stdout, &mime, args)
+            } else {
+                config.launch_handler(&mime, args)
+            }
         }
         Cmd::Get {
-            mime,
+            target,
             json,
             selector_args,
         } => {
             config.override_selector(selector_args);
-            config.show_handler(&mut stdout, &mime, json)
+            config.show_handler(&mut stdout, &target, json)
         }
         Cmd::Open {
             paths,
+            dry_run,
             selector_args,
         } => {
             config.override_selector(selector_args);
-            config.open_paths(&paths)
+            if dry_run {
+                config.show_open_cmds(&mut stdout, &paths)
+            } else {
+                config.open_paths(&paths)
+            }
         }
-        Cmd::Mime { paths, json } => {
-            mime_table(&mut stdout, &paths, json, config.terminal_output)
+        Cmd::Why { target, json } => {
+            config.show_explanation(&mut stdout, &target, json)
         }
+        Cmd::Regex { command } => match command {
+            RegexCmd::Set {
+                exec,
+                regexes,
+                terminal,
+                priority,
+            } => config.set_regex_handler(&exec, terminal, &regexes, priority),
+            RegexCmd::Add {
+                exec,
+                regexes,
+                terminal,
+                priority,
+            } => config.add_regex_handler(&exec, terminal, &regexes, priority),
+            RegexCmd::Remove { exec, regexes } => {
+                config.remove_regex_handler(&exec, &regexes)
+            }
+        },
+        Cmd::Config { command } => match command {
+            ConfigCmd::Get { key } => {
+                config.show_config(&mut stdout, key.as_deref())
+            }
+            ConfigCmd::Set { key, value } => config.set_config(&key, &value),
+        },
+        Cmd::Cache { command } => match command {
+            CacheCmd::Rebuild => config.rebuild_cache(),
+        },
+        Cmd::Mime { paths, json } => mime_table(
+            &mut stdout,
+            &paths,
+            &config.config.rewrites,
+            json,
+            config.terminal_output,
+        ),
         Cmd::List { all, json } => config.print(&mut stdout, all, json),
         Cmd::Unset { mime } => config.unset_handler(&mime),
         Cmd::Remove { mime, handler } => config.remove_handler(&mime, &handler),
+        Cmd::Removed { command } => match command {
+            RemovedCmd::Add { mime, handler } => {
+                config.add_removed_association(&mime, &handler)
+            }
+            RemovedCmd::Remove { mime, handler } => {
+                config.remove_removed_association(&mime, &handler)
+            }
+        },
     }
 }
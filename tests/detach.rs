@@ -0,0 +1,329 @@
+use insta_cmd::get_cargo_bin;
+use std::{
+    fs,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// Isolated XDG directories with a dummy handler for text files
+struct Environment {
+    dir: TempDir,
+}
+
+impl Environment {
+    /// Create a new environment whose dummy handler writes `started` in the temporary directory,
+    /// waits for as long as `hold` exists, then writes to stderr and its session ID to `output`
+    fn new(terminal: bool) -> Self {
+        let dir = TempDir::new().expect("Could not create temporary directory");
+        let root = dir.path();
+
+        for subdir in [
+            "config",
+            "config_dirs",
+            "data/applications",
+            "data_dirs",
+            "cache",
+        ] {
+            fs::create_dir_all(root.join(subdir))
+                .expect("Could not create XDG directory");
+        }
+
+        // A minimal MIME database is enough to detect the file's type
+        symlink(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets/mime"),
+            root.join("data_dirs/mime"),
+        )
+        .expect("Could not link MIME database");
+
+        // The handler gives up on waiting eventually, so a test cannot hang forever
+        let script = root.join("dummy.sh");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\ncd '{}'\ntouch started\ni=0\nwhile [ -e hold ] && [ $i -lt 200 ]; do sleep 0.05; i=$((i + 1)); done\necho finished >&2\ncut -d' ' -f6 /proc/$$/stat > output\n",
+                root.display()
+            ),
+        )
+        .expect("Could not write dummy handler");
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+            .expect("Could not make dummy handler executable");
+
+        let env = Self { dir };
+        env.add_entry(
+            "dummy.desktop",
+            &format!("{} %f", script.display()),
+            terminal,
+        );
+        env.set_defaults("dummy.desktop;");
+
+        fs::write(env.path("file.txt"), "Hello, world!\n")
+            .expect("Could not write text file");
+
+        env
+    }
+
+    /// Add a desktop entry for text files with the given command
+    fn add_entry(&self, name: &str, exec: &str, terminal: bool) {
+        fs::write(
+            self.path("data/applications").join(name),
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec={}\nTerminal={}\nMimeType=text/plain;\n",
+                name, exec, terminal
+            ),
+        )
+        .expect("Could not write desktop entry");
+    }
+
+    /// Set the default handlers for text files
+    fn set_defaults(&self, handlers: &str) {
+        self.set_defaults_for("text/plain", handlers);
+    }
+
+    /// Set the default handlers for the given mime
+    fn set_defaults_for(&self, mime: &str, handlers: &str) {
+        fs::write(
+            self.path("config/mimeapps.list"),
+            format!("[Default Applications]\n{}={}\n", mime, handlers),
+        )
+        .expect("Could not write mimeapps.list");
+    }
+
+    /// Write handlr's config file
+    fn configure(&self, config: &str) {
+        fs::create_dir_all(self.path("config/handlr"))
+            .expect("Could not create config directory");
+        fs::write(self.path("config/handlr/handlr.toml"), config)
+            .expect("Could not write config file");
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path)
+    }
+
+    /// Open a text file with handlr, checking that it succeeds
+    fn open(&self, terminal_output: bool) {
+        let output = self.run(terminal_output);
+        assert!(output.status.success());
+    }
+
+    /// Open a text file with handlr, returning its output
+    fn run(&self, terminal_output: bool) -> Output {
+        self.run_with(terminal_output, &self.path("file.txt"))
+    }
+
+    /// Open the given path/URL with handlr, returning its output
+    fn run_with(&self, terminal_output: bool, target: &Path) -> Output {
+        self.command(terminal_output, target)
+            .output()
+            .expect("Could not run handlr")
+    }
+
+    /// Start opening a text file with handlr without waiting for it
+    fn spawn(&self, terminal_output: bool) -> Child {
+        self.command(terminal_output, &self.path("file.txt"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Could not run handlr")
+    }
+
+    /// Build the command to open the given path/URL with handlr
+    fn command(&self, terminal_output: bool, target: &Path) -> Command {
+        let mut cmd = Command::new(get_cargo_bin("handlr"));
+        cmd.arg(format!("--force-terminal-output={}", terminal_output))
+            .arg("--disable-notifications")
+            .arg("open")
+            .arg(target)
+            .env("XDG_CONFIG_HOME", self.path("config"))
+            .env("XDG_CONFIG_DIRS", self.path("config_dirs"))
+            .env("XDG_DATA_HOME", self.path("data"))
+            .env("XDG_DATA_DIRS", self.path("data_dirs"))
+            .env("XDG_CACHE_HOME", self.path("cache"))
+            .env_remove("XDG_CURRENT_DESKTOP");
+        cmd
+    }
+
+    /// Make the dummy handler wait until `release` is called
+    fn hold(&self) {
+        fs::write(self.path("hold"), "").expect("Could not write hold file");
+    }
+
+    /// Let the dummy handler finish
+    fn release(&self) {
+        fs::remove_file(self.path("hold")).expect("Could not remove hold file");
+    }
+
+    /// Wait for the given file in the temporary directory to be written
+    fn wait_for(&self, path: &str) {
+        let path = self.path(path);
+        let start = Instant::now();
+
+        while !path.exists() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "Timed out waiting for `{}`",
+                path.display()
+            );
+            sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Wait for the dummy handler to write its session ID
+    fn wait_for_output(&self) -> String {
+        let output = self.path("output");
+        let start = Instant::now();
+
+        while !has_content(&output) {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "Timed out waiting for dummy handler"
+            );
+            sleep(Duration::from_millis(20));
+        }
+
+        fs::read_to_string(output)
+            .expect("Could not read output")
+            .trim()
+            .to_owned()
+    }
+}
+
+/// Check if a file exists and is not empty
+fn has_content(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0)
+}
+
+/// Get the session ID of the test process
+fn session_id() -> String {
+    fs::read_to_string("/proc/self/stat")
+        .expect("Could not read /proc/self/stat")
+        .rsplit_once(')')
+        .and_then(|(_, stat)| stat.split_whitespace().nth(3))
+        .expect("Could not parse /proc/self/stat")
+        .to_owned()
+}
+
+#[test]
+fn gui_handlers_are_detached() {
+    let env = Environment::new(false);
+    env.hold();
+
+    // handlr does not wait for the handler, which cannot finish until released
+    env.open(false);
+    assert!(!has_content(&env.path("output")));
+
+    // The handler runs in its own session
+    env.release();
+    assert_ne!(env.wait_for_output(), session_id());
+}
+
+#[test]
+fn watched_handlers_are_detached() {
+    let env = Environment::new(false);
+    env.hold();
+    env.configure("handler_grace_period = 200\n");
+
+    // handlr stops watching the handler once the grace period is over
+    env.open(false);
+    assert!(!has_content(&env.path("output")));
+
+    // The handler can still write to stderr once handlr has exited
+    env.release();
+    assert_ne!(env.wait_for_output(), session_id());
+}
+
+#[test]
+fn terminal_handlers_stay_attached() {
+    let env = Environment::new(true);
+    env.hold();
+
+    // handlr is still running while the handler is
+    let mut handlr = env.spawn(true);
+    env.wait_for("started");
+    assert!(handlr
+        .try_wait()
+        .expect("Could not check on handlr")
+        .is_none());
+
+    // handlr waits for the handler to finish
+    env.release();
+    assert!(handlr.wait().expect("Could not wait for handlr").success());
+    assert!(has_content(&env.path("output")));
+
+    // The handler runs in the same session as handlr
+    assert_eq!(env.wait_for_output(), session_id());
+}
+
+#[test]
+fn failing_handlers_are_reported() {
+    let env = Environment::new(false);
+    env.add_entry(
+        "failing.desktop",
+        r#"sh -c "echo 'something went wrong' >&2; exit 1""#,
+        false,
+    );
+    env.set_defaults("failing.desktop;dummy.desktop;");
+    env.configure("handler_grace_period = 2000\n");
+
+    let stderr = String::from_utf8(env.run(false).stderr)
+        .expect("handlr output is not valid UTF-8");
+    assert!(stderr.contains("'failing.desktop' exited unsuccessfully"));
+    assert!(stderr.contains("something went wrong"));
+
+    // The next handler is not tried
+    assert!(!env.path("started").exists());
+}
+
+#[test]
+fn failing_handlers_fall_back() {
+    let env = Environment::new(false);
+    env.add_entry("failing.desktop", r#"sh -c "exit 1""#, false);
+    env.set_defaults("failing.desktop;dummy.desktop;");
+    env.configure("handler_grace_period = 500\nfallback_on_failure = true\n");
+
+    env.open(false);
+
+    // The next handler is run instead
+    assert_ne!(env.wait_for_output(), session_id());
+}
+
+#[test]
+fn unspawnable_handlers_fall_back() {
+    let env = Environment::new(false);
+    env.add_entry("missing.desktop", "/nonexistent/program %f", false);
+    // The dummy handler is only associated with text files by its desktop entry
+    env.set_defaults("missing.desktop;");
+    env.configure("fallback_on_failure = true\n");
+
+    let stderr = String::from_utf8(env.run(false).stderr)
+        .expect("handlr output is not valid UTF-8");
+    assert!(stderr.contains("Could not run 'missing.desktop'"));
+
+    assert_ne!(env.wait_for_output(), session_id());
+}
+
+#[test]
+fn non_local_urls_fall_back() {
+    let env = Environment::new(false);
+    let script = env.path("dummy.sh");
+    env.add_entry("files.desktop", &format!("{} %f", script.display()), false);
+    env.add_entry("urls.desktop", &format!("{} %u", script.display()), false);
+    env.set_defaults_for(
+        "x-scheme-handler/sftp",
+        "files.desktop;urls.desktop;",
+    );
+    env.configure("fallback_on_failure = true\n");
+
+    let output = env.run_with(false, Path::new("sftp://example.com/file.txt"));
+    let stderr = String::from_utf8(output.stderr)
+        .expect("handlr output is not valid UTF-8");
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("only supports local files"));
+
+    // The handler that accepts URLs is run instead
+    assert_ne!(env.wait_for_output(), session_id());
+}